@@ -0,0 +1,251 @@
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
+use regex::Regex;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::discovery::RunFilter;
+use crate::issues::Strictness;
+use crate::measures::DiscoveryScope;
+use crate::output::OutputFormat;
+use crate::sqlite::SqliteScope;
+
+/// Takes revolve experimental data and assembles it in easy to read (pandas) TSV files
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Arguments {
+    /// Folder containing one sub-folder per experiment
+    #[arg(short, long, default_value = ".")]
+    pub data_root: PathBuf,
+
+    /// Experiment to consolidate (folder name under the data root), can be repeated.
+    /// When missing, every experiment under the data root is consolidated.
+    #[arg(short, long = "experiment")]
+    pub experiments: Vec<String>,
+
+    /// Runs to consolidate, as a comma separated list of numbers and ranges (e.g. `1-30,45,50-60`).
+    /// When missing, every run found in the experiment folders is consolidated.
+    #[arg(short, long)]
+    pub runs: Option<RunList>,
+
+    /// Only consolidate experiments whose name matches this regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    pub include_experiment: Vec<Regex>,
+
+    /// Skip experiments whose name matches this regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_experiment: Vec<Regex>,
+
+    /// Only consolidate runs whose folder name matches this regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    pub include_run: Vec<Regex>,
+
+    /// Skip runs whose folder name matches this regex, can be repeated
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_run: Vec<Regex>,
+
+    /// YAML configuration file with the measures, columns, input layout and outputs of each experiment
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Print the built-in configuration, as a starting point for a configuration file, and exit
+    #[arg(long)]
+    pub print_default_config: bool,
+
+    /// Also output the measures found in the descriptor files that are not listed in the configuration,
+    /// looking at the descriptors of each run on its own or of all the runs of the experiment
+    #[arg(long, value_enum, value_name = "SCOPE")]
+    pub discover_measures: Option<DiscoveryScope>,
+
+    /// Format of the generated files, can be repeated [default: from the configuration, tsv]
+    #[arg(short, long = "format", value_enum)]
+    pub formats: Vec<OutputFormat>,
+
+    /// Also write robots, snapshots, parents and measures in normalized SQLite tables,
+    /// with one database per experiment or one for the whole data root
+    #[arg(long, value_enum, value_name = "SCOPE")]
+    pub sqlite: Option<SqliteScope>,
+
+    /// After consolidating, merge the all_measures of the runs of each experiment in
+    /// `<experiment>/all_measures`, adding `experiment` and `run` columns
+    #[arg(long)]
+    pub combine: bool,
+
+    /// Like `--combine`, and also merge all the runs of all the experiments in a single `all_measures` file
+    #[arg(long)]
+    pub combine_root: bool,
+
+    /// How problems in the input files are handled: fail the run, warn and write the null token,
+    /// or write the null token and list the problems in the `issues` file of the run
+    #[arg(long, value_enum, default_value_t = Strictness::Lenient)]
+    pub strictness: Strictness,
+
+    /// Consolidate every run again, even the ones whose inputs did not change since the last consolidation
+    #[arg(long)]
+    pub force: bool,
+
+    /// Number of runs consolidated in parallel [default: number of cpus]
+    #[arg(short = 'j', long)]
+    pub workers: Option<NonZeroUsize>,
+
+    /// Folder where the consolidated files are written, mirroring `<experiment>/<run>/`.
+    /// Required unless `--in-place` is used.
+    #[arg(short, long, required_unless_present_any = ["in_place", "print_default_config"])]
+    pub output_dir: Option<PathBuf>,
+
+    /// Write the consolidated files inside each run folder of the data root, instead of in the output folder
+    #[arg(long, conflicts_with = "output_dir")]
+    pub in_place: bool,
+}
+
+impl Arguments {
+    /// Parses the command line arguments and exits with a usage message if they are not valid
+    pub fn parse_and_validate() -> Self {
+        let arguments = Self::parse();
+        if let Err(message) = arguments.validate() {
+            Self::command()
+                .error(ErrorKind::ValueValidation, message)
+                .exit();
+        }
+        arguments
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.data_root.is_dir() {
+            return Err(format!("data root {} is not a folder", self.data_root.display()));
+        }
+        for experiment in &self.experiments {
+            let experiment_path = self.data_root.join(experiment);
+            if !experiment_path.is_dir() {
+                return Err(format!("experiment folder {} does not exist", experiment_path.display()));
+            }
+        }
+        if let Some(output_dir) = &self.output_dir {
+            if output_dir.exists() && !output_dir.is_dir() {
+                return Err(format!("output path {} is not a folder", output_dir.display()));
+            }
+            let same_folder = match (output_dir.canonicalize(), self.data_root.canonicalize()) {
+                (Ok(output_dir), Ok(data_root)) => output_dir == data_root,
+                _ => false,
+            };
+            if same_folder {
+                return Err(format!(
+                    "output folder {} is the data root, use --in-place to write inside the run folders",
+                    output_dir.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Folder where the files that are not about a single run are written
+    pub fn output_root(&self) -> &Path {
+        self.output_dir.as_deref().unwrap_or(&self.data_root)
+    }
+
+    pub fn run_filter(&self) -> RunFilter<'_> {
+        RunFilter {
+            experiments: &self.experiments,
+            runs: self.runs.as_ref(),
+            include_experiments: &self.include_experiment,
+            exclude_experiments: &self.exclude_experiment,
+            include_runs: &self.include_run,
+            exclude_runs: &self.exclude_run,
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+            .map(NonZeroUsize::get)
+            .unwrap_or_else(num_cpus::get)
+    }
+}
+
+/// Ordered list of run numbers, parsed from a string like `1-30,45,50-60`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunList(Vec<u16>);
+
+impl RunList {
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn contains(&self, run: u16) -> bool {
+        self.0.binary_search(&run).is_ok()
+    }
+}
+
+impl FromStr for RunList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_run = |run: &str| {
+            run.trim()
+                .parse::<u16>()
+                .map_err(|e| format!("invalid run number \"{}\": {}", run.trim(), e))
+        };
+
+        let mut runs = Vec::new();
+        for item in s.split(',') {
+            if item.trim().is_empty() {
+                return Err(format!("empty element in run list \"{}\"", s));
+            }
+            match item.split_once('-') {
+                Some((start, end)) => {
+                    let start = parse_run(start)?;
+                    let end = parse_run(end)?;
+                    if start > end {
+                        return Err(format!("run range {}-{} is empty", start, end));
+                    }
+                    runs.extend(start..=end);
+                }
+                None => runs.push(parse_run(item)?),
+            }
+        }
+
+        runs.sort_unstable();
+        runs.dedup();
+        Ok(RunList(runs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(s: &str) -> Result<Vec<u16>, String> {
+        s.parse::<RunList>().map(|runs| runs.iter().collect())
+    }
+
+    #[test]
+    fn run_list_of_ranges_and_numbers() {
+        let expected: Vec<u16> = (1..=30).chain([45]).chain(50..=60).collect();
+        assert_eq!(runs("1-30,45,50-60").unwrap(), expected);
+    }
+
+    #[test]
+    fn run_list_is_sorted_and_deduplicated() {
+        assert_eq!(runs("5,1-3,2, 4 ,3-3").unwrap(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn run_list_contains() {
+        let list: RunList = "1-3,10".parse().unwrap();
+        assert!(list.contains(2));
+        assert!(list.contains(10));
+        assert!(!list.contains(4));
+    }
+
+    #[test]
+    fn run_list_errors() {
+        assert!(runs("").is_err());
+        assert!(runs("1,,2").is_err());
+        assert!(runs("1,").is_err());
+        assert!(runs("3-1").is_err());
+        assert!(runs("1-").is_err());
+        assert!(runs("-3").is_err());
+        assert!(runs("1-2-3").is_err());
+        assert!(runs("a").is_err());
+        assert!(runs("70000").is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use crate::data::vector::Vector2;
+use crate::error::{ConvertResult, Error};
+use crate::extra::ExtraColumn;
+use crate::output::OutputFormat;
+use crate::table::{ColumnDef, ColumnType};
+
+/// Columns of `all_measures`, in the order they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    RobotId,
+    Generation,
+    Species,
+    /// Age of the species in evaluations, from the species file
+    SpeciesEvaluations,
+    /// Age of the species in generations, from the species file
+    SpeciesGenerations,
+    /// Generations without improvement of the species, from the species file
+    SpeciesNoImprovements,
+    Fitness,
+    NParents,
+    Parent1,
+    Parent2,
+    PosStartX,
+    PosStartY,
+    PosEndX,
+    PosEndY,
+    /// Distance between the start and the end position
+    Displacement,
+    /// Direction from the start to the end position, in radians from the x axis
+    DisplacementAngle,
+    /// Distance between the end position and `target`, only written when a target is configured
+    DistanceToTarget,
+    /// Index of the best candidate in the `candidate_best` column of `extra.tsv`
+    CandidateBestIndex,
+    /// Score of the best candidate in the `candidate_best` column of `extra.tsv`
+    CandidateBestScore,
+    /// Length of the `candidates` column of `extra.tsv`
+    NCandidates,
+    /// All the other columns of `extra.tsv`, tuples expanded to one column per coordinate
+    ExtraColumns,
+    /// All the measures listed in `behavioural_measures`
+    BehaviouralMeasures,
+    /// All the measures listed in `phenotype_measures`
+    PhenotypeMeasures,
+}
+
+impl Column {
+    /// Header of the column, `None` for the measure groups that expand to one column per measure
+    pub fn header(&self) -> Option<&'static str> {
+        Some(match self {
+            Column::RobotId => "robot_id",
+            Column::Generation => "generation",
+            Column::Species => "species",
+            Column::SpeciesEvaluations => "species_evaluations",
+            Column::SpeciesGenerations => "species_generations",
+            Column::SpeciesNoImprovements => "species_no_improvements",
+            Column::Fitness => "fitness",
+            Column::NParents => "n_parents",
+            Column::Parent1 => "parent1",
+            Column::Parent2 => "parent2",
+            Column::PosStartX => "pos_start_x",
+            Column::PosStartY => "pos_start_y",
+            Column::PosEndX => "pos_end_x",
+            Column::PosEndY => "pos_end_y",
+            Column::Displacement => "displacement",
+            Column::DisplacementAngle => "displacement_angle",
+            Column::DistanceToTarget => "distance_to_target",
+            Column::CandidateBestIndex => "candidate_best_index",
+            Column::CandidateBestScore => "candidate_best_score",
+            Column::NCandidates => "n_candidates",
+            Column::ExtraColumns | Column::BehaviouralMeasures | Column::PhenotypeMeasures => return None,
+        })
+    }
+
+    pub fn column_type(&self) -> ColumnType {
+        match self {
+            Column::RobotId
+            | Column::Generation
+            | Column::Species
+            | Column::SpeciesEvaluations
+            | Column::SpeciesGenerations
+            | Column::SpeciesNoImprovements
+            | Column::NParents
+            | Column::Parent1
+            | Column::Parent2
+            | Column::CandidateBestIndex
+            | Column::NCandidates => ColumnType::UInt64,
+            Column::Fitness
+            | Column::PosStartX
+            | Column::PosStartY
+            | Column::PosEndX
+            | Column::PosEndY
+            | Column::Displacement
+            | Column::DisplacementAngle
+            | Column::DistanceToTarget
+            | Column::CandidateBestScore
+            | Column::BehaviouralMeasures
+            | Column::PhenotypeMeasures => ColumnType::Float64,
+            // typed from the values found, see `ExtraColumn`
+            Column::ExtraColumns => ColumnType::Utf8,
+        }
+    }
+}
+
+/// How the robots of each generation are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotLayout {
+    /// `species` if any generation folder of the run has species files, `identifiers` otherwise
+    Auto,
+    /// The identifiers file, one robot id per line, without species
+    Identifiers,
+    /// `species_<id>.yaml` files, with the robots of each species
+    Species,
+}
+
+/// Where the input files are found, relative to the run folder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputLayout {
+    /// Folder with one `generation_N` sub-folder per generation
+    pub generations: String,
+    /// Which files list the robots of a generation
+    pub snapshots: SnapshotLayout,
+    /// File listing the robots of a generation, inside each `generation_N` folder
+    pub identifiers: String,
+    /// File with the cosituated data of a generation, inside each `generation_N` folder
+    pub extra: String,
+    pub data_fullevolution: String,
+    /// Relative to `data_fullevolution`
+    pub fitness: String,
+    /// Folder with the `parents_<id>.yaml` files, relative to `data_fullevolution`
+    pub phylogeny: String,
+    /// Folder with the `phenotype_desc_<id>.txt` files, relative to `data_fullevolution`
+    pub phenotype_descriptors: String,
+    /// Folder with the `behavior_desc_<id>.txt` files, relative to `data_fullevolution`
+    pub behavioural_descriptors: String,
+}
+
+impl Default for InputLayout {
+    fn default() -> Self {
+        InputLayout {
+            generations: "generations".to_string(),
+            snapshots: SnapshotLayout::Auto,
+            identifiers: "identifiers.txt".to_string(),
+            extra: "extra.tsv".to_string(),
+            data_fullevolution: "data_fullevolution".to_string(),
+            fitness: "fitness.csv".to_string(),
+            phylogeny: "phylogeny".to_string(),
+            phenotype_descriptors: "descriptors".to_string(),
+            behavioural_descriptors: "descriptors/behavioural".to_string(),
+        }
+    }
+}
+
+/// Names of the generated files (without extension), `null` disables the file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputTargets {
+    /// Formats the generated tables are written in
+    pub formats: Vec<OutputFormat>,
+    pub all_measures: Option<String>,
+    pub phylogeny: Option<String>,
+    /// Phylogeny as a GraphML graph, with fitness, generation and species node attributes
+    pub phylogeny_graphml: Option<String>,
+    /// Phylogeny as a Graphviz DOT digraph
+    pub phylogeny_dot: Option<String>,
+    /// Phylogeny as Newick trees, only for runs where every robot has at most one parent
+    pub phylogeny_newick: Option<String>,
+    pub snapshots_ids: Option<String>,
+    /// Measures listed in the columns but absent from the descriptor file of a robot
+    pub missing_measures: Option<String>,
+    /// Problems found in the input files, only written with `--strictness report`
+    pub issues: Option<String>,
+    /// Size and age of each species in each generation, only written for runs with species files
+    pub species: Option<String>,
+    /// Individuals and proportion of each species in each generation, for Muller plots.
+    /// Like the other species dynamics files, only written for runs with species files.
+    pub muller_population: Option<String>,
+    /// Parent species of each species, for Muller plots
+    pub muller_edges: Option<String>,
+    /// Birth and extinction generation and parent of each species
+    pub species_lifespans: Option<String>,
+    /// Index and score of every candidate of every robot, from the `candidates` column of `extra.tsv`,
+    /// only written for runs with candidates
+    pub candidates: Option<String>,
+    /// Ancestors of the best robots of the last generation, with their fitness and phenotype measures
+    pub lineage: Option<String>,
+    /// Missing and incomplete inputs of the run, always written as JSON
+    pub quality_report: Option<String>,
+    /// Runs succeeded, failed and skipped per experiment, written as TSV and JSON in the output folder.
+    /// Only read from the `default` configuration.
+    pub batch_summary: Option<String>,
+}
+
+impl Default for OutputTargets {
+    fn default() -> Self {
+        OutputTargets {
+            formats: vec![OutputFormat::Tsv],
+            all_measures: Some("all_measures".to_string()),
+            phylogeny: Some("filogeny".to_string()),
+            phylogeny_graphml: None,
+            phylogeny_dot: None,
+            phylogeny_newick: None,
+            snapshots_ids: Some("snapshots_ids".to_string()),
+            missing_measures: Some("missing_measures".to_string()),
+            issues: Some("issues".to_string()),
+            species: Some("species".to_string()),
+            muller_population: Some("muller_population".to_string()),
+            muller_edges: Some("muller_edges".to_string()),
+            species_lifespans: Some("species_lifespans".to_string()),
+            candidates: Some("candidates".to_string()),
+            lineage: Some("lineage".to_string()),
+            quality_report: Some("quality_report".to_string()),
+            batch_summary: Some("batch_summary".to_string()),
+        }
+    }
+}
+
+/// How the runs of an experiment are read and consolidated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExperimentConfig {
+    /// Written in place of missing values
+    pub null_token: String,
+    pub columns: Vec<Column>,
+    pub behavioural_measures: Vec<String>,
+    pub phenotype_measures: Vec<String>,
+    /// Position the robots have to reach, enables the `distance_to_target` column
+    pub target: Option<Vector2<f64>>,
+    /// Number of best robots of the last generation whose ancestry is written to `lineage`
+    pub lineage_best: usize,
+    pub layout: InputLayout,
+    pub outputs: OutputTargets,
+}
+
+impl Default for ExperimentConfig {
+    fn default() -> Self {
+        ExperimentConfig {
+            null_token: "NA".to_string(),
+            columns: vec![
+                Column::RobotId,
+                Column::Generation,
+                Column::Species,
+                Column::SpeciesEvaluations,
+                Column::SpeciesGenerations,
+                Column::SpeciesNoImprovements,
+                Column::Fitness,
+                Column::NParents,
+                Column::Parent1,
+                Column::Parent2,
+                Column::PosStartX,
+                Column::PosStartY,
+                Column::PosEndX,
+                Column::PosEndY,
+                Column::Displacement,
+                Column::DisplacementAngle,
+                Column::DistanceToTarget,
+                Column::CandidateBestIndex,
+                Column::CandidateBestScore,
+                Column::NCandidates,
+                Column::ExtraColumns,
+                Column::BehaviouralMeasures,
+                Column::PhenotypeMeasures,
+            ],
+            behavioural_measures: [
+                "velocity",
+                "displacement_velocity",
+                "displacement_velocity_hill",
+                "head_balance",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            phenotype_measures: [
+                "branching",
+                "branching_modules_count",
+                "limbs",
+                "extremities",
+                "length_of_limbs",
+                "extensiveness",
+                "coverage",
+                "joints",
+                "hinge_count",
+                "active_hinges_count",
+                "brick_count",
+                "touch_sensor_count",
+                "brick_sensor_count",
+                "proportion",
+                "width",
+                "height",
+                "z_depth",
+                "absolute_size",
+                "sensors",
+                "symmetry",
+                "vertical_symmetry",
+                "height_base_ratio",
+                "base_density",
+                "bottom_layer",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            target: None,
+            lineage_best: 1,
+            layout: InputLayout::default(),
+            outputs: OutputTargets::default(),
+        }
+    }
+}
+
+impl ExperimentConfig {
+    /// Schema of `all_measures`, with the measure groups and the extra columns expanded
+    pub fn all_measures_columns(&self, extra_columns: &[ExtraColumn]) -> Vec<ColumnDef> {
+        let mut columns = Vec::new();
+        for column in &self.columns {
+            let column_type = column.column_type();
+            match column {
+                Column::BehaviouralMeasures => columns.extend(
+                    self.behavioural_measures
+                        .iter()
+                        .map(|measure| ColumnDef::new(measure, column_type)),
+                ),
+                Column::PhenotypeMeasures => columns.extend(
+                    self.phenotype_measures
+                        .iter()
+                        .map(|measure| ColumnDef::new(measure, column_type)),
+                ),
+                Column::DistanceToTarget if self.target.is_none() => {}
+                Column::ExtraColumns => columns.extend(extra_columns.iter().flat_map(ExtraColumn::column_defs)),
+                column => columns.extend(column.header().map(|header| ColumnDef::new(header, column_type))),
+            }
+        }
+        columns
+    }
+}
+
+/// Schema of the phylogeny file
+pub fn phylogeny_columns() -> Vec<ColumnDef> {
+    vec![
+        ColumnDef::new("robot_id", ColumnType::UInt64),
+        ColumnDef::new("n_parents", ColumnType::UInt64),
+        ColumnDef::new("parent1", ColumnType::UInt64),
+        ColumnDef::new("parent2", ColumnType::UInt64),
+    ]
+}
+
+/// Schema of the snapshots ids file
+pub fn snapshots_ids_columns() -> Vec<ColumnDef> {
+    vec![
+        ColumnDef::new("generation", ColumnType::UInt64),
+        ColumnDef::new("robot_id", ColumnType::UInt64),
+        ColumnDef::new("species_id", ColumnType::UInt64),
+    ]
+}
+
+/// Schema of the species file
+pub fn species_columns() -> Vec<ColumnDef> {
+    vec![
+        ColumnDef::new("generation", ColumnType::UInt64),
+        ColumnDef::new("species_id", ColumnType::UInt64),
+        ColumnDef::new("size", ColumnType::UInt64),
+        ColumnDef::new("age_evaluations", ColumnType::UInt64),
+        ColumnDef::new("age_generations", ColumnType::UInt64),
+        ColumnDef::new("no_improvements", ColumnType::UInt64),
+    ]
+}
+
+/// Schema of the missing measures file
+pub fn missing_measures_columns() -> Vec<ColumnDef> {
+    vec![
+        ColumnDef::new("robot_id", ColumnType::UInt64),
+        ColumnDef::new("descriptor", ColumnType::Utf8),
+        ColumnDef::new("measure", ColumnType::Utf8),
+    ]
+}
+
+/// Schema of the candidates file
+pub fn candidates_columns() -> Vec<ColumnDef> {
+    vec![
+        ColumnDef::new("generation", ColumnType::UInt64),
+        ColumnDef::new("robot_id", ColumnType::UInt64),
+        ColumnDef::new("candidate", ColumnType::UInt64),
+        ColumnDef::new("score", ColumnType::Float64),
+    ]
+}
+
+/// Configuration file: a `default` experiment configuration, and per experiment overrides of it
+///
+/// ```yaml
+/// default:
+///   null_token: NA
+///   behavioural_measures: [velocity, displacement_velocity]
+/// experiments:
+///   cosit_steadystate_5_120:
+///     behavioural_measures: [velocity, contacts]
+///     layout:
+///       extra: extra_data.tsv
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    default: Arc<ExperimentConfig>,
+    experiments: HashMap<String, Arc<ExperimentConfig>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    default: Option<Value>,
+    #[serde(default)]
+    experiments: HashMap<String, Value>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let config_str = fs::read_to_string(path)
+            .into_error(format!("could not read config file {}", path.display()))?;
+        let config_file: ConfigFile = serde_yaml::from_str(&config_str)
+            .into_error(format!("could not parse config file {}", path.display()))?;
+
+        let mut default_value = serde_yaml::to_value(ExperimentConfig::default())
+            .into_error("could not serialize default configuration")?;
+        if let Some(default_override) = config_file.default {
+            merge_values(&mut default_value, default_override);
+        }
+        let default: ExperimentConfig = serde_yaml::from_value(default_value.clone())
+            .into_error(format!("invalid default configuration in {}", path.display()))?;
+
+        let mut experiments = HashMap::new();
+        for (name, experiment_override) in config_file.experiments {
+            let mut experiment_value = default_value.clone();
+            merge_values(&mut experiment_value, experiment_override);
+            let experiment: ExperimentConfig = serde_yaml::from_value(experiment_value)
+                .into_error(format!("invalid configuration for experiment {} in {}", name, path.display()))?;
+            experiments.insert(name, Arc::new(experiment));
+        }
+
+        Ok(Config {
+            default: Arc::new(default),
+            experiments,
+        })
+    }
+
+    /// Configuration used for the given experiment
+    pub fn experiment(&self, name: &str) -> Arc<ExperimentConfig> {
+        self.experiments
+            .get(name)
+            .unwrap_or(&self.default)
+            .clone()
+    }
+
+    /// Configuration used for experiments that are not listed in the file
+    pub fn default_experiment(&self) -> Arc<ExperimentConfig> {
+        self.default.clone()
+    }
+
+    /// Replaces the output formats of every experiment
+    pub fn override_formats(&mut self, formats: &[OutputFormat]) {
+        for experiment in std::iter::once(&mut self.default).chain(self.experiments.values_mut()) {
+            Arc::make_mut(experiment).outputs.formats = formats.to_vec();
+        }
+    }
+
+    /// YAML for the built-in configuration, as a starting point for a config file
+    pub fn default_yaml() -> String {
+        #[derive(Serialize)]
+        struct DefaultFile {
+            default: ExperimentConfig,
+        }
+        serde_yaml::to_string(&DefaultFile {
+            default: ExperimentConfig::default(),
+        })
+        .expect("default configuration is always serializable")
+    }
+}
+
+/// Recursively replaces the entries of `base` with the ones in `overrides`.
+/// Mappings are merged key by key, everything else (lists included) is replaced.
+fn merge_values(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Mapping(base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
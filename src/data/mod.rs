@@ -1 +1,2 @@
-pub mod vector;
\ No newline at end of file
+pub mod vector;
+pub mod python;
\ No newline at end of file
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+use crate::error::Error;
+
+/// A python literal, as printed by `repr()` or by numpy
+#[derive(Debug, Clone, PartialEq)]
+pub enum PyValue {
+    None,
+    Bool(bool),
+    Int(i64),
+    /// Also `nan`, `inf` and `-inf`
+    Float(f64),
+    Str(String),
+    Tuple(Vec<PyValue>),
+    /// Also numpy arrays printed without commas, e.g. `[1. 2.]`
+    List(Vec<PyValue>),
+}
+
+impl PyValue {
+    /// Parses a whole python literal, surrounding whitespace is ignored
+    pub fn parse<S: AsRef<str>>(data: S) -> Result<Self, Error> {
+        let data = data.as_ref();
+        let mut parser = Parser {
+            data,
+            chars: data.char_indices().peekable(),
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some((position, _)) => Err(parser.error(position, "unexpected trailing characters")),
+        }
+    }
+
+    /// The number as a float, integers converted
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PyValue::Int(value) => Some(*value as f64),
+            PyValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The elements of a tuple or of a list
+    pub fn as_sequence(&self) -> Option<&[PyValue]> {
+        match self {
+            PyValue::Tuple(values) | PyValue::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The elements of a tuple or list of `n` numbers, each parsed as `F`
+    pub fn parse_numbers<F>(&self, n: usize) -> Result<Vec<F>, Error>
+    where
+        F: std::str::FromStr,
+    {
+        let values = self
+            .as_sequence()
+            .filter(|values| values.len() == n)
+            .ok_or_else(|| Error::new(format!("expected a sequence of {} numbers, found {}", n, self)))?;
+        values
+            .iter()
+            .map(|value| match value {
+                PyValue::Int(_) | PyValue::Float(_) => value
+                    .to_string()
+                    .parse::<F>()
+                    .map_err(|_| Error::new(format!("number {} out of range", value))),
+                _ => Err(Error::new(format!("expected a number, found {}", value))),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PyValue {
+    /// Formats the value like python's `repr()`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_sequence = |f: &mut fmt::Formatter<'_>, values: &[PyValue]| -> fmt::Result {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", value)?;
+            }
+            Ok(())
+        };
+        match self {
+            PyValue::None => write!(f, "None"),
+            PyValue::Bool(true) => write!(f, "True"),
+            PyValue::Bool(false) => write!(f, "False"),
+            PyValue::Int(value) => write!(f, "{}", value),
+            PyValue::Float(value) if value.is_nan() => write!(f, "nan"),
+            PyValue::Float(value) if value.is_infinite() => {
+                write!(f, "{}", if *value > 0.0 { "inf" } else { "-inf" })
+            }
+            PyValue::Float(value) => write!(f, "{:?}", value),
+            PyValue::Str(value) => {
+                write!(f, "'")?;
+                for c in value.chars() {
+                    match c {
+                        '\'' => write!(f, "\\'")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "'")
+            }
+            PyValue::Tuple(values) if values.len() == 1 => write!(f, "({},)", values[0]),
+            PyValue::Tuple(values) => {
+                write!(f, "(")?;
+                write_sequence(f, values)?;
+                write!(f, ")")
+            }
+            PyValue::List(values) => {
+                write!(f, "[")?;
+                write_sequence(f, values)?;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    data: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, position: usize, message: &str) -> Error {
+        Error::new(format!("invalid python literal {:?}: {} at {}", self.data, message, position))
+    }
+
+    fn end_error(&self) -> Error {
+        self.error(self.data.len(), "unexpected end")
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn value(&mut self) -> Result<PyValue, Error> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            None => Err(self.end_error()),
+            Some((_, '(')) => {
+                self.chars.next();
+                let (mut values, trailing_comma) = self.sequence(')')?;
+                // `(x)` is just `x`, `(x,)` is a tuple
+                if values.len() == 1 && !trailing_comma {
+                    Ok(values.remove(0))
+                } else {
+                    Ok(PyValue::Tuple(values))
+                }
+            }
+            Some((_, '[')) => {
+                self.chars.next();
+                Ok(PyValue::List(self.sequence(']')?.0))
+            }
+            Some((_, quote @ '\'')) | Some((_, quote @ '"')) => {
+                self.chars.next();
+                self.string(quote)
+            }
+            Some((start, _)) => self.token(start),
+        }
+    }
+
+    /// Elements up to `close`, separated by commas or, numpy style, by whitespace only.
+    /// Also returns whether the last element is followed by a comma.
+    fn sequence(&mut self, close: char) -> Result<(Vec<PyValue>, bool), Error> {
+        let mut values = Vec::new();
+        let mut trailing_comma = false;
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek().copied() {
+                None => return Err(self.end_error()),
+                Some((_, c)) if c == close => {
+                    self.chars.next();
+                    return Ok((values, trailing_comma));
+                }
+                Some((position, ',')) => return Err(self.error(position, "unexpected comma")),
+                Some(_) => {}
+            }
+            values.push(self.value()?);
+            self.skip_whitespace();
+            trailing_comma = self.chars.next_if(|(_, c)| *c == ',').is_some();
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<PyValue, Error> {
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err(self.end_error()),
+                Some((_, c)) if c == quote => return Ok(PyValue::Str(value)),
+                Some((_, '\\')) => match self.chars.next() {
+                    None => return Err(self.end_error()),
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c)) => value.push(c),
+                },
+                Some((_, c)) => value.push(c),
+            }
+        }
+    }
+
+    /// `None`, `True`, `False` or a number
+    fn token(&mut self, start: usize) -> Result<PyValue, Error> {
+        let mut end = start;
+        while let Some((position, c)) = self
+            .chars
+            .next_if(|(_, c)| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+        {
+            end = position + c.len_utf8();
+        }
+        let token = &self.data[start..end];
+        match token {
+            "" => Err(self.error(start, "unexpected character")),
+            "None" => Ok(PyValue::None),
+            "True" => Ok(PyValue::Bool(true)),
+            "False" => Ok(PyValue::Bool(false)),
+            token => {
+                if let Ok(value) = token.parse::<i64>() {
+                    Ok(PyValue::Int(value))
+                } else if let Ok(value) = token.parse::<f64>() {
+                    Ok(PyValue::Float(value))
+                } else {
+                    Err(self.error(start, &format!("unknown value {:?}", token)))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn float(data: &str) -> f64 {
+        match PyValue::parse(data).unwrap() {
+            PyValue::Float(value) => value,
+            other => panic!("{:?} parsed as {:?}", data, other),
+        }
+    }
+
+    #[test]
+    fn parse_scalars() {
+        assert_eq!(PyValue::parse("None").unwrap(), PyValue::None);
+        assert_eq!(PyValue::parse("True").unwrap(), PyValue::Bool(true));
+        assert_eq!(PyValue::parse(" -3 ").unwrap(), PyValue::Int(-3));
+        assert_eq!(PyValue::parse("0.25").unwrap(), PyValue::Float(0.25));
+    }
+
+    #[test]
+    fn parse_nan_and_inf() {
+        assert!(float("nan").is_nan());
+        assert_eq!(float("inf"), f64::INFINITY);
+        assert_eq!(float("-inf"), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn parse_scientific_notation() {
+        assert_eq!(float("1e-3"), 0.001);
+        assert_eq!(float("-4.873e-02"), -0.04873);
+        assert_eq!(float("1.5E+10"), 1.5e10);
+    }
+
+    #[test]
+    fn parse_numpy_array() {
+        assert_eq!(
+            PyValue::parse("[1. 2.]").unwrap(),
+            PyValue::List(vec![PyValue::Float(1.0), PyValue::Float(2.0)])
+        );
+        assert_eq!(
+            PyValue::parse("[ 0.5  -1.e-02\n 3 ]").unwrap(),
+            PyValue::List(vec![PyValue::Float(0.5), PyValue::Float(-0.01), PyValue::Int(3)])
+        );
+    }
+
+    #[test]
+    fn parse_single_element_tuple() {
+        assert_eq!(PyValue::parse("(4,)").unwrap(), PyValue::Tuple(vec![PyValue::Int(4)]));
+        assert_eq!(PyValue::parse("(4)").unwrap(), PyValue::Int(4));
+        assert_eq!(PyValue::parse("()").unwrap(), PyValue::Tuple(vec![]));
+    }
+
+    #[test]
+    fn parse_nested_tuples() {
+        assert_eq!(
+            PyValue::parse("((1, 2.5), [(3, None)])").unwrap(),
+            PyValue::Tuple(vec![
+                PyValue::Tuple(vec![PyValue::Int(1), PyValue::Float(2.5)]),
+                PyValue::List(vec![PyValue::Tuple(vec![PyValue::Int(3), PyValue::None])]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_strings_with_escapes() {
+        assert_eq!(PyValue::parse(r"'it\'s'").unwrap(), PyValue::Str("it's".to_string()));
+        assert_eq!(PyValue::parse(r#""a\"b\\c\n\td""#).unwrap(), PyValue::Str("a\"b\\c\n\td".to_string()));
+        assert_eq!(PyValue::parse(r#"'say "hi", (x)'"#).unwrap(), PyValue::Str("say \"hi\", (x)".to_string()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(PyValue::parse("[1,,2]").is_err());
+        assert!(PyValue::parse("[,1]").is_err());
+        assert!(PyValue::parse("(1, 2").is_err());
+        assert!(PyValue::parse("'abc").is_err());
+        assert!(PyValue::parse(r"'abc\").is_err());
+        assert!(PyValue::parse("").is_err());
+        assert!(PyValue::parse("1 2").is_err());
+        assert!(PyValue::parse("(1, 2) x").is_err());
+        assert!(PyValue::parse("[1, 2]]").is_err());
+        assert!(PyValue::parse("nope").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for data in ["None", "(1,)", "[1, 2.5, 'a\\'b']", "((1, 2), [])", "nan", "-inf", "1e-05"] {
+            let value = PyValue::parse(data).unwrap();
+            let reparsed = PyValue::parse(value.to_string()).unwrap();
+            assert_eq!(value.to_string(), reparsed.to_string(), "{}", data);
+        }
+    }
+
+    #[test]
+    fn parse_numbers_of_sequence() {
+        let value = PyValue::parse("(1, -2.5)").unwrap();
+        assert_eq!(value.parse_numbers::<f64>(2).unwrap(), vec![1.0, -2.5]);
+        assert!(value.parse_numbers::<f64>(3).is_err());
+        assert!(PyValue::parse("(1, 'a')").unwrap().parse_numbers::<f64>(2).is_err());
+        assert!(PyValue::parse("3").unwrap().parse_numbers::<f64>(1).is_err());
+    }
+}
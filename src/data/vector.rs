@@ -1,13 +1,15 @@
-use regex::Regex;
-use crate::error::{ConvertError, ConvertResult, Error};
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, Mul, Sub};
+use crate::data::python::PyValue;
+use crate::error::Error;
 
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Vector2<F> {
     pub x: F,
     pub y: F,
 }
 
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Vector3<F> {
     pub x: F,
     pub y: F,
@@ -19,22 +21,17 @@ impl<F> Vector2<F> {
         Self {x, y}
     }
 
+    /// Parses a python tuple or list of two numbers, e.g. `(0.5, -1e-3)` or `[0.5 -0.001]`
     pub fn parse_from_python<S>(data: S) -> Result<Self, Error>
     where
         S: AsRef<str>,
         F: std::str::FromStr,
-        F::Err: 'static + std::error::Error,
     {
-        lazy_static! {
-                static ref VECTOR2_PYTHON_REGEX: Regex =
-                    Regex::new(r"^\(([-+]?([0-9]*[.])?[0-9]+([eE][-+]?\d+)?), ([-+]?([0-9]*[.])?[0-9]+([eE][-+]?\d+)?)\)$").unwrap();
-            }
-        let captured = VECTOR2_PYTHON_REGEX.captures(data.as_ref())
-            .ok_or(Error::new(format!("Failed to parse the python vector from {}", data.as_ref())))?;
-
-        let x = captured[1].parse::<F>().into_error("can't parse x")?;
-        let y = captured[2].parse::<F>().into_error("can't parse y")?;
-        Ok(Self { x, y })
+        let mut coordinates = PyValue::parse(data)?.parse_numbers::<F>(2)?.into_iter();
+        match (coordinates.next(), coordinates.next()) {
+            (Some(x), Some(y)) => Ok(Self { x, y }),
+            _ => unreachable!("parse_numbers returns two coordinates"),
+        }
     }
 }
 
@@ -43,22 +40,95 @@ impl<F> Vector3<F> {
         Self {x, y, z}
     }
 
+    /// Parses a python tuple or list of three numbers, e.g. `(1, 0.5, nan)`
     pub fn parse_from_python<S>(data: S) -> Result<Self, Error>
-        where
-            S: AsRef<str>,
-            F: std::str::FromStr,
-            F::Err: 'static + std::error::Error,
+    where
+        S: AsRef<str>,
+        F: std::str::FromStr,
     {
-        lazy_static! {
-                static ref VECTOR3_PYTHON_REGEX: Regex =
-                    Regex::new(r"^\((-?\d+), (-?\d+), (-?\d+)\)$").unwrap();
-            }
-        let captured = VECTOR3_PYTHON_REGEX.captures(data.as_ref())
-            .ok_or(Error::new("Failed to parse the python vector"))?;
-
-        let x = captured[1].parse::<F>().into_error("can't parse x")?;
-        let y = captured[2].parse::<F>().into_error("can't parse y")?;
-        let z = captured[3].parse::<F>().into_error("can't parse z")?;
-        Ok(Self { x, y, z })
-    }
-}
\ No newline at end of file
+        let mut coordinates = PyValue::parse(data)?.parse_numbers::<F>(3)?.into_iter();
+        match (coordinates.next(), coordinates.next(), coordinates.next()) {
+            (Some(x), Some(y), Some(z)) => Ok(Self { x, y, z }),
+            _ => unreachable!("parse_numbers returns three coordinates"),
+        }
+    }
+}
+
+impl<F: Add<Output = F>> Add for Vector2<F> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl<F: Sub<Output = F>> Sub for Vector2<F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl<F: Copy + Add<Output = F> + Mul<Output = F>> Vector2<F> {
+    pub fn scale(self, factor: F) -> Self {
+        Self::new(self.x * factor, self.y * factor)
+    }
+
+    pub fn dot(self, other: Self) -> F {
+        self.x * other.x + self.y * other.y
+    }
+}
+
+impl Vector2<f64> {
+    pub fn norm(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Angle from the x axis in radians, in (-pi, pi]
+    pub fn heading(self) -> f64 {
+        self.y.atan2(self.x)
+    }
+
+    /// Unsigned angle between the two vectors in radians, NaN if one of them is zero
+    pub fn angle(self, other: Self) -> f64 {
+        (self.dot(other) / (self.norm() * other.norm())).clamp(-1.0, 1.0).acos()
+    }
+}
+
+impl<F: Add<Output = F>> Add for Vector3<F> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl<F: Sub<Output = F>> Sub for Vector3<F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl<F: Copy + Add<Output = F> + Mul<Output = F>> Vector3<F> {
+    pub fn scale(self, factor: F) -> Self {
+        Self::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+
+    pub fn dot(self, other: Self) -> F {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+}
+
+impl Vector3<f64> {
+    pub fn norm(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Unsigned angle between the two vectors in radians, NaN if one of them is zero
+    pub fn angle(self, other: Self) -> f64 {
+        (self.dot(other) / (self.norm() * other.norm())).clamp(-1.0, 1.0).acos()
+    }
+}
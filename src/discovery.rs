@@ -0,0 +1,127 @@
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use crate::cli::RunList;
+use crate::config::{Config, InputLayout};
+use crate::error::{ConvertResult, Error};
+
+/// A run found under the data root, identified by its experiment and run folder names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunId {
+    pub experiment: String,
+    pub run: String,
+}
+
+/// Which experiments and runs are selected for consolidation
+#[derive(Debug, Default)]
+pub struct RunFilter<'a> {
+    /// If not empty, only these experiments are considered
+    pub experiments: &'a [String],
+    /// If set, only runs with these numbers are considered
+    pub runs: Option<&'a RunList>,
+    pub include_experiments: &'a [Regex],
+    pub exclude_experiments: &'a [Regex],
+    pub include_runs: &'a [Regex],
+    pub exclude_runs: &'a [Regex],
+}
+
+impl RunFilter<'_> {
+    fn accepts_experiment(&self, experiment: &str) -> bool {
+        (self.experiments.is_empty() || self.experiments.iter().any(|e| e == experiment))
+            && matches_filters(experiment, self.include_experiments, self.exclude_experiments)
+    }
+
+    fn accepts_run(&self, run: &str) -> bool {
+        let listed = match self.runs {
+            Some(runs) => run.parse::<u16>().map(|run| runs.contains(run)).unwrap_or(false),
+            None => true,
+        };
+        listed && self.matches_run_filters(run)
+    }
+
+    fn matches_run_filters(&self, run: &str) -> bool {
+        matches_filters(run, self.include_runs, self.exclude_runs)
+    }
+}
+
+fn matches_filters(name: &str, include: &[Regex], exclude: &[Regex]) -> bool {
+    (include.is_empty() || include.iter().any(|regex| regex.is_match(name)))
+        && !exclude.iter().any(|regex| regex.is_match(name))
+}
+
+/// A folder looks like a revolve run if it contains both `generations/` and `data_fullevolution/`
+pub fn is_revolve_run<P: AsRef<Path>>(path: P, layout: &InputLayout) -> bool {
+    path.as_ref().join(&layout.generations).is_dir() && path.as_ref().join(&layout.data_fullevolution).is_dir()
+}
+
+/// Walks `data_root/<experiment>/<run>` and returns every revolve run accepted by the filter,
+/// sorted by experiment name and then by run number.
+///
+/// Runs that were explicitly requested in `filter.runs`, and not left out by the run regex filters,
+/// but have no folder or whose folder is not a revolve run are reported on stderr.
+pub fn discover_runs<P: AsRef<Path>>(data_root: P, filter: &RunFilter, config: &Config) -> Result<Vec<RunId>, Error> {
+    let data_root = data_root.as_ref();
+    let mut experiments = list_folders(data_root)?;
+    experiments.retain(|experiment| filter.accepts_experiment(experiment));
+    experiments.sort();
+
+    let mut found = Vec::new();
+    for experiment in experiments {
+        let experiment_path = data_root.join(&experiment);
+        let layout = &config.experiment(&experiment).layout;
+        let folders = list_folders(&experiment_path)?;
+        let mut runs: Vec<String> = folders
+            .iter()
+            .filter(|run| filter.accepts_run(run) && is_revolve_run(experiment_path.join(run), layout))
+            .cloned()
+            .collect();
+        runs.sort_by(|a, b| compare_run_names(a, b));
+
+        if let Some(requested) = filter.runs {
+            for run in requested.iter() {
+                let run_folders: Vec<&String> =
+                    folders.iter().filter(|folder| folder.parse::<u16>() == Ok(run)).collect();
+                let selected_folders: Vec<&&String> =
+                    run_folders.iter().filter(|folder| filter.matches_run_filters(folder)).collect();
+                if run_folders.is_empty() && filter.matches_run_filters(&run.to_string()) {
+                    eprintln!("{}:{} has no run folder, skipping", experiment, run);
+                } else if !selected_folders.is_empty()
+                    && !selected_folders.iter().any(|folder| is_revolve_run(experiment_path.join(folder), layout))
+                {
+                    eprintln!("{}:{} is not a revolve run folder, skipping", experiment, run);
+                }
+            }
+        }
+
+        found.extend(runs.into_iter().map(|run| RunId {
+            experiment: experiment.clone(),
+            run,
+        }));
+    }
+
+    Ok(found)
+}
+
+fn list_folders(path: &Path) -> Result<Vec<String>, Error> {
+    let mut folders = Vec::new();
+    for entry in fs::read_dir(path).into_error(format!("Could not read folder {}", path.display()))? {
+        let entry = entry.into_error(format!("Could not read folder {}", path.display()))?;
+        if entry.path().is_dir() {
+            if let Some(name) = entry.file_name().to_str() {
+                folders.push(name.to_string());
+            }
+        }
+    }
+    Ok(folders)
+}
+
+/// Numeric run names are sorted by value, others alphabetically after them
+pub fn compare_run_names(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
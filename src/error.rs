@@ -1,18 +1,138 @@
 use std;
-use std::fmt::Debug;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Source of an `Error::Other`, can be sent between worker threads
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
 #[derive(Debug)]
-#[allow(dead_code)]
-pub struct Error {
-    pub message: String,
-    pub source_error: Option<Box<dyn std::error::Error>>
+pub enum Error {
+    /// An input file or folder that does not exist
+    MissingFile {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A line of an input file that could not be parsed
+    MalformedLine {
+        path: PathBuf,
+        /// Starting from 1
+        line: usize,
+        /// Name of the field, when the problem is in a single one
+        column: Option<String>,
+        message: String,
+    },
+    /// A measure of descriptor files that is not in the configuration
+    UnknownMeasure {
+        /// First descriptor file the measure was found in
+        path: PathBuf,
+        measure: String,
+        /// Number of descriptor files with the measure
+        descriptors: usize,
+    },
+    /// A measure of the configuration that is absent from descriptor files
+    MissingMeasure {
+        /// First descriptor file the measure is absent from
+        path: PathBuf,
+        measure: String,
+        /// Number of descriptor files without the measure
+        descriptors: usize,
+    },
+    /// Input files that contradict each other
+    InconsistentData {
+        path: PathBuf,
+        message: String,
+    },
+    /// Everything else, e.g. writing an output failed
+    Other {
+        message: String,
+        source: Option<BoxedError>,
+    },
 }
 
 impl Error {
     pub fn new<S: ToString>(message: S) -> Self {
-        Error {
+        Error::Other {
+            message: message.to_string(),
+            source: None,
+        }
+    }
+
+    pub fn malformed_line<P, S>(path: P, line: usize, column: Option<&str>, message: S) -> Self
+    where
+        P: AsRef<Path>,
+        S: ToString,
+    {
+        Error::MalformedLine {
+            path: path.as_ref().to_path_buf(),
+            line,
+            column: column.map(str::to_string),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn inconsistent_data<P: AsRef<Path>, S: ToString>(path: P, message: S) -> Self {
+        Error::InconsistentData {
+            path: path.as_ref().to_path_buf(),
             message: message.to_string(),
-            source_error: None,
+        }
+    }
+
+    /// Short name of the variant, e.g. `malformed_line`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::MissingFile { .. } => "missing_file",
+            Error::MalformedLine { .. } => "malformed_line",
+            Error::UnknownMeasure { .. } => "unknown_measure",
+            Error::MissingMeasure { .. } => "missing_measure",
+            Error::InconsistentData { .. } => "inconsistent_data",
+            Error::Other { .. } => "other",
+        }
+    }
+
+    /// Error opening or reading `path`: `MissingFile` if it does not exist
+    pub fn io<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        if source.kind() == io::ErrorKind::NotFound {
+            Error::MissingFile { path, source }
+        } else {
+            Error::Other {
+                message: format!("could not read {}", path.display()),
+                source: Some(Box::new(source)),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingFile { path, .. } => write!(f, "{}: file not found", path.display()),
+            Error::MalformedLine { path, line, column: Some(column), message } => {
+                write!(f, "{}:{}: {}: {}", path.display(), line, column, message)
+            }
+            Error::MalformedLine { path, line, column: None, message } => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            Error::UnknownMeasure { path, measure, descriptors } => {
+                write!(f, "{}: unknown measure {} (descriptor files: {})", path.display(), measure, descriptors)
+            }
+            Error::MissingMeasure { path, measure, descriptors } => {
+                write!(f, "{}: missing measure {} (descriptor files: {})", path.display(), measure, descriptors)
+            }
+            Error::InconsistentData { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::Other { message, source: Some(source) } => write!(f, "{}: {}", message, source),
+            Error::Other { message, source: None } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::MissingFile { source, .. } => Some(source),
+            Error::Other { source: Some(source), .. } => Some(source.as_ref()),
+            _ => None,
         }
     }
 }
@@ -21,11 +141,11 @@ pub trait ConvertError {
     fn into_error<S: ToString>(self, message: S) -> Error;
 }
 
-impl<T: 'static + std::error::Error> ConvertError for T {
+impl<T: 'static + std::error::Error + Send + Sync> ConvertError for T {
     fn into_error<S: ToString>(self, message: S) -> Error {
-        Error {
+        Error::Other {
             message: message.to_string(),
-            source_error: Some(Box::new(self)),
+            source: Some(Box::new(self)),
         }
     }
 }
@@ -39,12 +159,3 @@ impl<T, E: ConvertError> ConvertResult<T> for Result<T, E> {
         self.map_err(|e| e.into_error::<S>(message))
     }
 }
-
-// impl<T> ConvertResult<T> for Option<T> {
-//     fn into_error<S: ToString>(self, message: S) -> Result<T, Error> {
-//         self.ok_or(Error {
-//             message: message.to_string(),
-//             source_error: None
-//         })
-//     }
-// }
@@ -0,0 +1,149 @@
+use crate::data::python::PyValue;
+use crate::data::vector::{Vector2, Vector3};
+use crate::table::{ColumnDef, ColumnType, Value};
+
+/// A field of `extra.tsv`, typed from its text
+#[derive(Debug, Clone)]
+pub enum ExtraValue {
+    Null,
+    UInt(u64),
+    Float(f64),
+    Vector2(Vector2<f64>),
+    Vector3(Vector3<f64>),
+    Text(String),
+}
+
+impl ExtraValue {
+    /// `None`, integers, floats, strings and python tuples or lists of 2 or 3 numbers, anything else is kept as text
+    pub fn parse(field: &str) -> Self {
+        let field = field.trim();
+        if field.is_empty() {
+            return ExtraValue::Null;
+        }
+        let value = match PyValue::parse(field) {
+            Ok(value) => value,
+            Err(_) => return ExtraValue::Text(field.to_string()),
+        };
+        match value {
+            PyValue::None => ExtraValue::Null,
+            PyValue::Str(text) => ExtraValue::Text(text),
+            PyValue::Int(value) if value >= 0 => ExtraValue::UInt(value as u64),
+            PyValue::Int(_) | PyValue::Float(_) => value.as_f64().map(ExtraValue::Float).unwrap_or(ExtraValue::Null),
+            value => match value.as_sequence().map(<[PyValue]>::len) {
+                Some(2) => value.parse_numbers::<f64>(2).ok().map(|c| ExtraValue::Vector2(Vector2::new(c[0], c[1]))),
+                Some(3) => value
+                    .parse_numbers::<f64>(3)
+                    .ok()
+                    .map(|c| ExtraValue::Vector3(Vector3::new(c[0], c[1], c[2]))),
+                _ => None,
+            }
+            .unwrap_or_else(|| ExtraValue::Text(field.to_string())),
+        }
+    }
+
+    fn shape(&self) -> Option<ExtraShape> {
+        match self {
+            ExtraValue::Null => None,
+            ExtraValue::UInt(_) => Some(ExtraShape::UInt),
+            ExtraValue::Float(_) => Some(ExtraShape::Float),
+            ExtraValue::Vector2(_) => Some(ExtraShape::Vector2),
+            ExtraValue::Vector3(_) => Some(ExtraShape::Vector3),
+            ExtraValue::Text(_) => Some(ExtraShape::Text),
+        }
+    }
+}
+
+/// How the values of an extra column are written, the common type of all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraShape {
+    UInt,
+    Float,
+    /// Expanded to `<name>_x` and `<name>_y`
+    Vector2,
+    /// Expanded to `<name>_x`, `<name>_y` and `<name>_z`
+    Vector3,
+    /// Written as found in the file
+    Text,
+}
+
+impl ExtraShape {
+    fn merge(self, other: ExtraShape) -> ExtraShape {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ExtraShape::UInt, ExtraShape::Float) | (ExtraShape::Float, ExtraShape::UInt) => ExtraShape::Float,
+            _ => ExtraShape::Text,
+        }
+    }
+}
+
+/// A column of `extra.tsv` that is not read by the consolidation itself, carried through to `all_measures`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraColumn {
+    pub name: String,
+    pub shape: ExtraShape,
+}
+
+impl ExtraColumn {
+    pub fn column_defs(&self) -> Vec<ColumnDef> {
+        let float_columns = |suffixes: &[&str]| {
+            suffixes
+                .iter()
+                .map(|suffix| ColumnDef::new(format!("{}_{}", self.name, suffix), ColumnType::Float64))
+                .collect()
+        };
+        match self.shape {
+            ExtraShape::UInt => vec![ColumnDef::new(&self.name, ColumnType::UInt64)],
+            ExtraShape::Float => vec![ColumnDef::new(&self.name, ColumnType::Float64)],
+            ExtraShape::Vector2 => float_columns(&["x", "y"]),
+            ExtraShape::Vector3 => float_columns(&["x", "y", "z"]),
+            ExtraShape::Text => vec![ColumnDef::new(&self.name, ColumnType::Utf8)],
+        }
+    }
+
+    /// Values of the field in the columns of `column_defs`, nulls if the robot has no such field
+    pub fn values(&self, field: Option<&str>) -> Vec<Value> {
+        let value = field.map(ExtraValue::parse).unwrap_or(ExtraValue::Null);
+        match (self.shape, value) {
+            (ExtraShape::UInt, ExtraValue::UInt(v)) => vec![Value::UInt(v)],
+            (ExtraShape::Float, ExtraValue::UInt(v)) => vec![Value::Float(v as f64)],
+            (ExtraShape::Float, ExtraValue::Float(v)) => vec![Value::Float(v)],
+            (ExtraShape::Vector2, ExtraValue::Vector2(v)) => vec![Value::Float(v.x), Value::Float(v.y)],
+            (ExtraShape::Vector3, ExtraValue::Vector3(v)) => {
+                vec![Value::Float(v.x), Value::Float(v.y), Value::Float(v.z)]
+            }
+            (ExtraShape::Text, ExtraValue::Null) => vec![Value::Null],
+            (ExtraShape::Text, ExtraValue::Text(text)) => vec![Value::Str(text)],
+            (ExtraShape::Text, _) => vec![Value::Str(field.unwrap_or_default().trim().to_string())],
+            (_, _) => vec![Value::Null; self.column_defs().len()],
+        }
+    }
+}
+
+/// Columns of the `(name, field)` pairs of all the `extra.tsv` rows, in order of appearance,
+/// each with the type that fits all its values
+pub fn infer_extra_columns<'a, I>(fields: I) -> Vec<ExtraColumn>
+where
+    I: IntoIterator<Item = &'a (String, String)>,
+{
+    let mut columns: Vec<(String, Option<ExtraShape>)> = Vec::new();
+    for (name, field) in fields {
+        let shape = ExtraValue::parse(field).shape();
+        match columns.iter_mut().find(|(column_name, _)| column_name == name) {
+            Some((_, column_shape)) => {
+                *column_shape = match (*column_shape, shape) {
+                    (Some(a), Some(b)) => Some(a.merge(b)),
+                    (a, b) => a.or(b),
+                }
+            }
+            None => columns.push((name.clone(), shape)),
+        }
+    }
+    columns
+        .into_iter()
+        .map(|(name, shape)| ExtraColumn {
+            name,
+            // only nulls, the column is still written
+            shape: shape.unwrap_or(ExtraShape::Float),
+        })
+        .collect()
+}
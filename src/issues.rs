@@ -0,0 +1,91 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::error::Error;
+use crate::output::{write_table, OutputFormat};
+use crate::table::{ColumnDef, ColumnType, Table, Value};
+
+/// What to do with problems in the input files of a run
+/// (missing files, unparsable values, unknown or missing measures)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Strictness {
+    /// Fail the run on the first problem
+    Strict,
+    /// Print a warning, write the null token in place of the value and go on
+    #[default]
+    Lenient,
+    /// Like lenient, but collect the problems in the issues file of the run instead of printing them
+    Report,
+}
+
+/// Applies the strictness policy to the problems found while consolidating a run
+#[derive(Debug)]
+pub struct Issues {
+    strictness: Strictness,
+    collected: Vec<Error>,
+}
+
+impl Issues {
+    pub fn new(strictness: Strictness) -> Self {
+        Issues {
+            strictness,
+            collected: Vec::new(),
+        }
+    }
+
+    /// Returns the error in strict mode, otherwise records it and lets the caller skip the value
+    pub fn tolerate(&mut self, error: Error) -> Result<(), Error> {
+        match self.strictness {
+            Strictness::Strict => return Err(error),
+            Strictness::Lenient => eprintln!("Warning: {}", error),
+            Strictness::Report => self.collected.push(error),
+        }
+        Ok(())
+    }
+
+    /// Writes the collected problems, one per row, if the policy is to report them
+    pub fn write_report<P: AsRef<Path>>(&self, stem: P, formats: &[OutputFormat], null_token: &str) -> Result<(), Error> {
+        if self.strictness != Strictness::Report {
+            return Ok(());
+        }
+        let mut table = Table::new(issues_columns());
+        for error in &self.collected {
+            let (path, line, column, message) = match error {
+                Error::MissingFile { path, .. } => (Some(path), None, None, "file not found".to_string()),
+                Error::MalformedLine { path, line, column, message } => {
+                    (Some(path), Some(*line as u64), column.clone(), message.clone())
+                }
+                Error::UnknownMeasure { path, measure, descriptors } => {
+                    let message = format!("unknown measure (descriptor files: {})", descriptors);
+                    (Some(path), None, Some(measure.clone()), message)
+                }
+                Error::MissingMeasure { path, measure, descriptors } => {
+                    let message = format!("missing measure (descriptor files: {})", descriptors);
+                    (Some(path), None, Some(measure.clone()), message)
+                }
+                Error::InconsistentData { path, message } => (Some(path), None, None, message.clone()),
+                Error::Other { .. } => (None, None, None, error.to_string()),
+            };
+            table.push(vec![
+                Value::Str(error.kind().to_string()),
+                path.map(|path| Value::Str(path.display().to_string())).unwrap_or(Value::Null),
+                line.into(),
+                column.map(Value::Str).unwrap_or(Value::Null),
+                Value::Str(message),
+            ]);
+        }
+        write_table(&table, stem, formats, null_token)
+    }
+}
+
+/// Schema of the issues file
+pub fn issues_columns() -> Vec<ColumnDef> {
+    vec![
+        ColumnDef::new("kind", ColumnType::Utf8),
+        ColumnDef::new("path", ColumnType::Utf8),
+        ColumnDef::new("line", ColumnType::UInt64),
+        ColumnDef::new("column", ColumnType::Utf8),
+        ColumnDef::new("message", ColumnType::Utf8),
+    ]
+}
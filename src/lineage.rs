@@ -0,0 +1,104 @@
+use std::collections::{HashMap, HashSet};
+use crate::table::{ColumnDef, ColumnType, Table, Value};
+
+/// Line of descent of the best robots of the last generation of a run, built from its `all_measures` table
+/// and the fitness of its robots, where robots that were not evaluated have no fitness.
+/// Going back from each of the `best` fittest robots of the last generation, each step moves to the fittest
+/// parent, until a robot of the initial population (without parents) is reached.
+/// One row per ancestor: the robot the lineage starts from, the number of steps back, and the generation,
+/// fitness and `measures` of the ancestor (null if unknown).
+pub fn lineage_table(
+    all_measures: &Table,
+    fitness: &HashMap<u64, Option<f64>>,
+    phylogeny: &HashMap<u64, Vec<u64>>,
+    measures: &[String],
+    best: usize,
+) -> Table {
+    let column = |name: &str| all_measures.columns.iter().position(|column| column.name == name);
+    let mut columns = vec![
+        ColumnDef::new("best_robot_id", ColumnType::UInt64),
+        ColumnDef::new("step", ColumnType::UInt64),
+        ColumnDef::new("robot_id", ColumnType::UInt64),
+        ColumnDef::new("generation", ColumnType::UInt64),
+        ColumnDef::new("fitness", ColumnType::Float64),
+    ];
+    let measure_columns: Vec<Option<usize>> = measures.iter().map(|measure| column(measure)).collect();
+    columns.extend(measures.iter().map(|measure| ColumnDef::new(measure, ColumnType::Float64)));
+    let mut lineage = Table::new(columns);
+
+    let robot_id_column = match column("robot_id") {
+        Some(robot_id_column) => robot_id_column,
+        None => return lineage,
+    };
+    let generation_column = column("generation");
+    let uint = |row: &[Value], column: Option<usize>| match column.map(|i| &row[i]) {
+        Some(Value::UInt(value)) => Some(*value),
+        _ => None,
+    };
+    let float = |row: &[Value], column: Option<usize>| match column.map(|i| &row[i]) {
+        Some(Value::Float(value)) => Some(*value),
+        Some(Value::UInt(value)) => Some(*value as f64),
+        _ => None,
+    };
+
+    // first row of each robot, the one of the generation it was born in
+    let mut robots: HashMap<u64, &Vec<Value>> = HashMap::new();
+    for row in &all_measures.rows {
+        if let Some(robot_id) = uint(row, Some(robot_id_column)) {
+            let first_row = robots.entry(robot_id).or_insert(row);
+            if uint(row, generation_column) < uint(first_row, generation_column) {
+                *first_row = row;
+            }
+        }
+    }
+    let fitness = |robot_id: &u64| fitness.get(robot_id).copied().flatten();
+
+    let last_generation = all_measures.rows.iter().filter_map(|row| uint(row, generation_column)).max();
+    let mut last_robots: Vec<(u64, f64)> = all_measures
+        .rows
+        .iter()
+        .filter(|row| last_generation.is_some() && uint(row, generation_column) == last_generation)
+        .filter_map(|row| uint(row, Some(robot_id_column)))
+        .filter_map(|robot_id| Some((robot_id, fitness(&robot_id)?)))
+        .collect();
+    last_robots.sort_by(|(id_a, fitness_a), (id_b, fitness_b)| {
+        fitness_b.partial_cmp(fitness_a).unwrap_or(std::cmp::Ordering::Equal).then(id_a.cmp(id_b))
+    });
+    last_robots.dedup_by_key(|(robot_id, _)| *robot_id);
+
+    for (best_robot_id, _) in last_robots.into_iter().take(best) {
+        let mut visited = HashSet::new();
+        let mut robot_id = best_robot_id;
+        for step in 0.. {
+            let row = robots.get(&robot_id);
+            let mut lineage_row = vec![
+                Value::UInt(best_robot_id),
+                Value::UInt(step),
+                Value::UInt(robot_id),
+                row.and_then(|row| uint(row, generation_column)).into(),
+                fitness(&robot_id).into(),
+            ];
+            lineage_row.extend(
+                measure_columns
+                    .iter()
+                    .map(|measure_column| row.and_then(|row| float(row, *measure_column)).into()),
+            );
+            lineage.push(lineage_row);
+            visited.insert(robot_id);
+
+            // fittest parent, the first one listed on ties or if no fitness is known
+            let parent_fitness = |parent: &u64| fitness(parent).unwrap_or(f64::NEG_INFINITY);
+            let parent = phylogeny.get(&robot_id).and_then(|parents| {
+                parents.iter().copied().fold(None, |best: Option<u64>, parent| match best {
+                    Some(best) if parent_fitness(&parent) <= parent_fitness(&best) => Some(best),
+                    _ => Some(parent),
+                })
+            });
+            match parent {
+                Some(parent) if !visited.contains(&parent) => robot_id = parent,
+                _ => break,
+            }
+        }
+    }
+    lineage
+}
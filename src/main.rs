@@ -8,330 +8,355 @@ extern crate yaml_rust;
 pub mod iterators;
 pub mod error;
 pub mod data;
+pub mod cli;
+pub mod paths;
+pub mod discovery;
+pub mod config;
+pub mod measures;
+pub mod table;
+pub mod output;
+pub mod sqlite;
+pub mod manifest;
+pub mod issues;
+pub mod quality;
+pub mod summary;
+pub mod muller;
+pub mod extra;
+pub mod yaml;
+pub mod phylogeny;
+pub mod lineage;
 
-use iterators::IdentifyLast;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::io::prelude::*;
-use std::{collections::HashMap, fs, io, path::Path};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs, io, path::Path};
 use std::collections::hash_map::Entry;
-use std::ops::Range;
 use error::{Error, ConvertResult};
-use crate::data::vector::{Vector2, Vector3};
+use crate::data::python::PyValue;
+use crate::data::vector::Vector2;
+use crate::cli::Arguments;
+use crate::paths::RunPaths;
+use crate::discovery::{compare_run_names, discover_runs};
+use crate::config::{candidates_columns, missing_measures_columns, phylogeny_columns, snapshots_ids_columns, species_columns, Column, Config, ExperimentConfig, SnapshotLayout};
+use std::sync::{mpsc, Arc};
+use crate::measures::{load_descriptor, missing_measures, Descriptor, DiscoveredMeasures, DiscoveryScope, MeasureMismatches};
+use crate::discovery::RunId;
+use crate::table::{Table, Value};
+use crate::output::{read_table, remove_table, write_table};
+use crate::manifest::Manifest;
+use crate::issues::{Issues, Strictness};
+use crate::quality::{DescriptorKind, QualityReport};
+use crate::summary::{BatchSummary, RunStatus};
+use crate::muller::SpeciesDynamics;
+use crate::extra::infer_extra_columns;
+use crate::phylogeny::{PhylogenyGraph, RobotNode};
+use crate::lineage::lineage_table;
+use std::time::Instant;
+use std::panic::{self, AssertUnwindSafe};
+use crate::sqlite::{RobotRecord, RunRecords, SqliteDatabase};
+use std::path::PathBuf;
 use threadpool::ThreadPool;
 
-const PANDAS_NULL: &str = "NA";
-
-const DIR_PATH: &str = "/home/matteo/projects/phd/revolve/experiments/isaac/data";
-const EXPERIMENT_TYPES: &[&str] = &[
-    // "base_test",
-    // "base_test_120s",
-    // "base_prog",
-    // "base_rnd",
-    // "cosit_prog",
-    // "cosit_rnd",
-    // "cosit_rnd_zdepth",
-    "cosit_steadystate_5_120",
-    // "cosit_steadystate_area_5_120",
-];
-
-const RUNS: Range<u16> = 1..159;
-// const RUNS: &[u16] = &[
-    // 1, 2, 3, 4, 5, 6, 7, 8, 9, 10,
-    // 11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
-    // 21, 22, 23, 24, 25, 26, 27, 28, 29, 30,
-    // 31, 32, 33, 34, 35, 36, 37, 38,
-// ];
-
-const BEHAVIOURAL_MEASURES: &[&str] = &[
-    "velocity",
-    "displacement_velocity",
-    "displacement_velocity_hill",
-    "head_balance",
-    // "contacts",
-];
-
-const PHENOTYPE_MEASURES: &[&str] = &[
-    "branching",
-    "branching_modules_count",
-    "limbs",
-    "extremities",
-    "length_of_limbs",
-    "extensiveness",
-    "coverage",
-    "joints",
-    "hinge_count",
-    "active_hinges_count",
-    "brick_count",
-    "touch_sensor_count",
-    "brick_sensor_count",
-    "proportion",
-    "width",
-    "height",
-    "z_depth",
-    "absolute_size",
-    "sensors",
-    "symmetry",
-    "vertical_symmetry",
-    "height_base_ratio",
-    "base_density",
-    "bottom_layer",
-];
-
-fn open_file_with_headers<P: AsRef<Path>>(path: &P) -> io::Result<fs::File> {
-    let file_all_measures = path.as_ref().join("all_measures.tsv");
-    let mut file_summary = fs::File::create(file_all_measures)?;
-
-    //WRITE ID + GENERATION + SPECIES_ID + FITNESS + N_PARENTS + PARENT_1 + PARENT_2
-    for (last, header) in [
-        "robot_id",
-        "generation",
-        "species",
-        "fitness",
-        "n_parents",
-        "parent1",
-        "parent2",
-        "pos_start_x",
-        "pos_start_y",
-        "pos_end_x",
-        "pos_end_y",
-    ]
-    .iter()
-    .chain(BEHAVIOURAL_MEASURES.iter())
-    .chain(PHENOTYPE_MEASURES.iter())
-    .identify_last()
-    {
-        if !last {
-            write!(&mut file_summary, "{}\t", header)?;
-        } else {
-            writeln!(&mut file_summary, "{}", header)?;
-        }
-    }
-
-    Ok(file_summary)
-}
-
 fn load_yaml_to_str<P: AsRef<Path>>(path: &P) -> io::Result<String> {
-    Ok(fs::read_to_string(path.as_ref())?
-        .replace(":", ": ")
-        .replace("None", "null"))
+    Ok(yaml::normalize(&fs::read_to_string(path.as_ref())?))
 }
 
-fn generate_all_measures<P: AsRef<Path>>(
-    run_path: &P,
-    id_gen_species_map: &HashMap<u64, (Vec<(u64, u64, Vector2<f64>, Vector2<f64>)>, Option<CosituatedData>)>,
+/// Writes all_measures and the phylogeny files, returns all_measures and the fitness of every robot
+fn generate_all_measures(
+    run_paths: &RunPaths,
+    config: &ExperimentConfig,
+    id_gen_species_map: &SnapshotMap,
     phylogeny: &HashMap<u64, Vec<u64>>,
-) -> Result<(), Error> {
-    let mut file_summary = open_file_with_headers(run_path).into_error("could not open file_summary")?;
+    mut records: Option<&mut RunRecords>,
+    issues: &mut Issues,
+    quality: &mut QualityReport,
+) -> Result<(Table, HashMap<u64, Option<f64>>), Error> {
+    let extra_columns = infer_extra_columns(
+        id_gen_species_map
+            .values()
+            .flatten()
+            .filter_map(|snapshot| snapshot.extra.as_ref())
+            .flat_map(|extra| &extra.other_columns),
+    );
+    let mut all_measures = Table::new(config.all_measures_columns(&extra_columns));
 
-    let phylogeny_filepath = run_path
-        .as_ref()
-        .join("data_fullevolution")
-        .join("filogeny.tsv");
-    let mut phylogeny_file =
-        fs::File::create(phylogeny_filepath).into_error("Cound not create finlogeny file")?;
+    let mut phylogeny_table = Table::new(phylogeny_columns());
+    let mut phylogeny_nodes = BTreeMap::new();
 
-    let fitness_filepath = run_path
-        .as_ref()
-        .join("data_fullevolution")
-        .join("fitness.csv");
-    let fitness_file =
-        io::BufReader::new(fs::File::open(fitness_filepath).into_error("could not open fitness file")?);
-
-    fitness_file
-        .lines()
-        .map(|line| line.unwrap())
-        .map(|line| {
-            let mut line_split = line.split(',');
-            let robot_id: u64 = line_split.next().unwrap().parse::<u64>().unwrap();
-            let fitness: Option<f64> = line_split.next().unwrap().parse::<f64>().ok();
-            assert_eq!(None, line_split.next());
-            (robot_id, fitness)
-        })
+    let mut missing_measures_table = Table::new(missing_measures_columns());
+    let mut checked_robots: HashSet<u64> = HashSet::new();
+    let mut behaviour_mismatches = MeasureMismatches::default();
+    let mut phenotype_mismatches = MeasureMismatches::default();
+
+    let fitnesses = load_fitness(&run_paths.fitness(), issues)?;
+    let rows = fitnesses
+        .iter()
+        .copied()
         .flat_map(move |(robot_id, fitness)| {
-            // replicate line for each gen and species id found
-            lazy_static! {
-                static ref DEFAULT_VALUE: Vec<(Option<u64>, Option<u64>, Option<Vector2<f64>>, Option<Vector2<f64>>)> = vec![(None, None, None, None)];
-            }
-            id_gen_species_map
-                .get(&robot_id)
-                .map(move |(slice, _cosituated_data)| {
-                    slice
-                        .iter()
-                        .map(|(generation, species, pos_start, pos_end)| (Some(*generation), Some(*species), Some(*pos_start), Some(*pos_end)))
-                        .collect::<Vec<_>>()
-                })
-                .as_ref()
-                .unwrap_or(&DEFAULT_VALUE)
-                .iter()
-                .map(move |(generation, species, pos_start, pos_end)| {
-                    (robot_id, generation.clone(), species.clone(), fitness, pos_start.clone(), pos_end.clone())
-                })
-                .collect::<Vec<(u64, Option<u64>, Option<u64>, Option<f64>, Option<Vector2<f64>>, Option<Vector2<f64>>)>>()
+            // replicate line for each snapshot the robot is found in, once without snapshot data if none
+            let snapshots: Vec<Option<&Snapshot>> = match id_gen_species_map.get(&robot_id) {
+                Some(snapshots) => snapshots.iter().map(Some).collect(),
+                None => vec![None],
+            };
+            snapshots.into_iter().map(move |snapshot| (robot_id, snapshot, fitness))
         })
         .map(
-            |(robot_id, generation, species_id, fitness, pos_start, pos_end): (
-                u64,
-                Option<u64>,
-                Option<u64>,
-                Option<f64>,
-                Option<Vector2<f64>>,
-                Option<Vector2<f64>>,
-            )| {
+            |(robot_id, snapshot, fitness)| {
                 // add phylogeny data
                 static NO_PARENTS: Vec<u64> = Vec::new();
                 let parents: &Vec<u64> = phylogeny.get(&robot_id).unwrap_or(&NO_PARENTS);
                 let n_parents = parents.len();
-                let parent1 = parents.get(0);
+                let parent1 = parents.first();
                 let parent2 = parents.get(1);
-                (
-                    robot_id, generation, species_id, fitness, n_parents, parent1, parent2, pos_start, pos_end
-                )
+                (robot_id, snapshot, fitness, n_parents, parent1, parent2)
             },
-        )
-        // Add extra cosituated data
-        // .map(|(robot_id, generation, species_id, fitness, n_parents, parent1, parent2, pos_start, pos_end)| {
-        //     // let () = generate_shaphot_ids.get(&robot_id)
-        //     //     .map(|| ());
-        //     (robot_id, generation, species_id, fitness, n_parents, parent1, parent2, pos_start, pos_end)
-        // })
-        .for_each(
-            |(robot_id, generation, species_id, fitness, n_parents, parent1, parent2, start_pos, end_pos)| {
-                // WRITE ID + GENERATION + SPECIES_ID + FITNESS + N_PARENTS + PARENT_1 + PARENT_2
-                let fitness = fitness.unwrap_or(0.0);
-
-                let parent1 = parent1.map(|id| id.to_string());
-                let parent1 = parent1
-                    .as_ref()
-                    .map(|string| string.as_str())
-                    .unwrap_or(PANDAS_NULL);
-
-                let parent2 = parent2.map(|id| id.to_string());
-                let parent2 = parent2
-                    .as_ref()
-                    .map(|string| string.as_str())
-                    .unwrap_or(PANDAS_NULL);
-
-                writeln!(
-                    &mut phylogeny_file,
-                    "{}\t{}\t{}\t{}",
-                    robot_id, n_parents, parent1, parent2
-                )
-                .unwrap();
-
-                let generation = generation.map(|id| id.to_string());
-                let generation = generation
-                    .as_ref()
-                    .map(|string| string.as_str())
-                    .unwrap_or(PANDAS_NULL);
-
-                let species_id = species_id.map(|id| id.to_string());
-                let species_id = species_id
-                    .as_ref()
-                    .map(|string| string.as_str())
-                    .unwrap_or(PANDAS_NULL);
-
-                let start_pos = start_pos.unwrap_or_default();
-                let end_pos = end_pos.unwrap_or_default();
-
-                write!(
-                    &mut file_summary,
-                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t",
-                    robot_id, generation, species_id, fitness, n_parents, parent1, parent2, start_pos.x, start_pos.y, end_pos.x, end_pos.y
-                )
-                .unwrap();
-
-                // BEHAVIOURAL MEASURES -------------------------------------------
-                let behaviour_filename = run_path
-                    .as_ref()
-                    .join("data_fullevolution")
-                    .join("descriptors")
-                    .join("behavioural")
-                    .join(format!("behavior_desc_{}.txt", robot_id));
-
-                if let Ok(behaviour_file) = fs::File::open(behaviour_filename) {
-                    let mut file_reader = io::BufReader::new(behaviour_file).lines().peekable();
-                    let first_line = file_reader.peek().unwrap().as_ref().unwrap();
-                    if first_line == "None" {
-                        for _ in BEHAVIOURAL_MEASURES {
-                            write!(&mut file_summary, "{}\t", PANDAS_NULL).unwrap();
-                        }
-                    } else {
-                        let behavior_measures = file_reader
-                            .map(|line| {
-                                let line = line.unwrap();
-                                let mut split = line.trim().split(' ');
-                                let measure = split.next().unwrap().to_string();
-                                let value = split.next().unwrap().parse::<f64>().ok();
-                                assert_eq!(None, split.next());
-                                (measure, value)
-                            })
-                            .collect::<HashMap<String, Option<f64>>>();
-
-                        for measure in BEHAVIOURAL_MEASURES {
-                            let value = behavior_measures
-                                .get(*measure)
-                                .unwrap()
-                                .map(|v| v.to_string());
-                            let value = value.as_ref().map(|v| v.as_str()).unwrap_or(PANDAS_NULL);
-                            write!(&mut file_summary, "{}\t", value).unwrap();
-                        }
+        );
+
+    let mut loaded_robot: Option<u64> = None;
+    let mut behaviour_measures: Option<Descriptor> = None;
+    let mut phenotype_measures: Option<Descriptor> = None;
+    for (robot_id, snapshot, fitness, n_parents, parent1, parent2) in rows {
+        let extra = snapshot.and_then(|snapshot| snapshot.extra.as_ref());
+        let start_pos = extra.map(|extra| extra.initial_position);
+        let end_pos = extra.map(|extra| extra.final_position);
+        let displacement = extra.map(|extra| extra.final_position - extra.initial_position);
+        let candidate_best = extra.and_then(|extra| extra.candidate_best);
+        let species_age = snapshot.and_then(|snapshot| snapshot.species_age);
+        // a robot is in multiple rows when it survives multiple generations
+        let first_row_of_robot = checked_robots.insert(robot_id);
+
+        if first_row_of_robot && fitness.is_none() {
+            quality.none_fitness.push(robot_id);
+        }
+
+        // WRITE ID + N_PARENTS + PARENT_1 + PARENT_2
+        if first_row_of_robot {
+            phylogeny_table.push(vec![
+                Value::UInt(robot_id),
+                Value::UInt(n_parents as u64),
+                parent1.copied().into(),
+                parent2.copied().into(),
+            ]);
+            phylogeny_nodes.insert(robot_id, RobotNode {
+                fitness,
+                generation: snapshot.map(|snapshot| snapshot.generation),
+                species: snapshot.and_then(|snapshot| snapshot.species),
+            });
+        }
+
+        // the rows of a robot are consecutive, its descriptors are read only once
+        if loaded_robot != Some(robot_id) {
+            let behaviour_path = run_paths.behaviour_descriptor(robot_id);
+            let phenotype_path = run_paths.phenotype_descriptor(robot_id);
+            behaviour_measures = load_descriptor(&behaviour_path, issues)?;
+            phenotype_measures = load_descriptor(&phenotype_path, issues)?;
+            loaded_robot = Some(robot_id);
+            quality.check_descriptor(DescriptorKind::Behavioural, robot_id, &behaviour_path, &behaviour_measures);
+            quality.check_descriptor(DescriptorKind::Phenotype, robot_id, &phenotype_path, &phenotype_measures);
+            for (path, descriptor, measures, mismatches) in [
+                (&behaviour_path, &behaviour_measures, &config.behavioural_measures, &mut behaviour_mismatches),
+                (&phenotype_path, &phenotype_measures, &config.phenotype_measures, &mut phenotype_mismatches),
+            ] {
+                if let Some(descriptor) = descriptor {
+                    mismatches.check(path, descriptor, measures);
+                }
+            }
+        }
+
+        if let Some(records) = &mut records {
+            if first_row_of_robot {
+                records.robots.push(RobotRecord {
+                    robot_id,
+                    fitness,
+                    start_position: start_pos,
+                    end_position: end_pos,
+                });
+                for (descriptor, measures) in [
+                    (&behaviour_measures, &mut records.behaviour_measures),
+                    (&phenotype_measures, &mut records.phenotype_measures),
+                ] {
+                    if let Some(descriptor) = descriptor {
+                        let mut descriptor: Vec<_> = descriptor.iter().collect();
+                        descriptor.sort_by(|a, b| a.0.cmp(b.0));
+                        measures.extend(descriptor.into_iter().map(|(measure, value)| (robot_id, measure.clone(), *value)));
                     }
-                } else {
-                    for _ in BEHAVIOURAL_MEASURES {
-                        write!(&mut file_summary, "{}\t", PANDAS_NULL).unwrap();
+                }
+            }
+        }
+
+        if first_row_of_robot {
+            for (kind, descriptor, measures) in [
+                ("behavioural", &behaviour_measures, &config.behavioural_measures),
+                ("phenotype", &phenotype_measures, &config.phenotype_measures),
+            ] {
+                if let Some(descriptor) = descriptor {
+                    for measure in missing_measures(descriptor, measures) {
+                        missing_measures_table.push(vec![
+                            Value::UInt(robot_id),
+                            Value::Str(kind.to_string()),
+                            Value::Str(measure.to_string()),
+                        ]);
                     }
                 }
+            }
+        }
 
-                // PHENOTYPES MEASURES --------------------------------------------
-                let phenotype_filename = run_path
-                    .as_ref()
-                    .join("data_fullevolution")
-                    .join("descriptors")
-                    .join(format!("phenotype_desc_{}.txt", robot_id));
-                if let Ok(phenotype_file) = fs::File::open(phenotype_filename) {
-                    let phenotype_measures = io::BufReader::new(phenotype_file)
-                        .lines()
-                        .map(|line| {
-                            let line = line.unwrap();
-                            let mut split = line.trim().split(' ');
-                            let measure = split.next().unwrap().to_string();
-                            let value = split.next().unwrap().parse::<f64>().ok();
-                            assert_eq!(None, split.next());
-                            (measure, value)
-                        })
-                        .collect::<HashMap<String, Option<f64>>>();
-
-                    for (last, measure) in PHENOTYPE_MEASURES.iter().identify_last() {
-                        let value = phenotype_measures
-                            .get(*measure)
-                            .unwrap()
-                            .map(|v| v.to_string());
-                        let value = value.as_ref().map(|v| v.as_str()).unwrap_or(PANDAS_NULL);
-                        if last {
-                            writeln!(&mut file_summary, "{}", value).unwrap();
-                        } else {
-                            write!(&mut file_summary, "{}\t", value).unwrap();
-                        }
+        let mut row: Vec<Value> = Vec::new();
+        for column in &config.columns {
+            match column {
+                Column::RobotId => row.push(Value::UInt(robot_id)),
+                Column::Generation => row.push(snapshot.map(|snapshot| snapshot.generation).into()),
+                Column::Species => row.push(snapshot.and_then(|snapshot| snapshot.species).into()),
+                Column::SpeciesEvaluations => row.push(species_age.map(|age| age.evaluations).into()),
+                Column::SpeciesGenerations => row.push(species_age.map(|age| age.generations).into()),
+                Column::SpeciesNoImprovements => row.push(species_age.map(|age| age.no_improvements).into()),
+                Column::Fitness => row.push(fitness.into()),
+                Column::NParents => row.push(Value::UInt(n_parents as u64)),
+                Column::Parent1 => row.push(parent1.copied().into()),
+                Column::Parent2 => row.push(parent2.copied().into()),
+                Column::PosStartX => row.push(start_pos.map(|position| position.x).into()),
+                Column::PosStartY => row.push(start_pos.map(|position| position.y).into()),
+                Column::PosEndX => row.push(end_pos.map(|position| position.x).into()),
+                Column::PosEndY => row.push(end_pos.map(|position| position.y).into()),
+                Column::Displacement => row.push(displacement.map(Vector2::norm).into()),
+                Column::DisplacementAngle => row.push(displacement.map(Vector2::heading).into()),
+                Column::DistanceToTarget => {
+                    if let Some(target) = config.target {
+                        row.push(extra.map(|extra| (target - extra.final_position).norm()).into());
                     }
-                } else {
-                    for (last, _) in PHENOTYPE_MEASURES.iter().identify_last() {
-                        if last {
-                            writeln!(&mut file_summary, "{}", PANDAS_NULL).unwrap();
-                        } else {
-                            write!(&mut file_summary, "{}\t", PANDAS_NULL).unwrap();
-                        }
+                }
+                Column::CandidateBestIndex => row.push(candidate_best.map(|(index, _)| index as u64).into()),
+                Column::CandidateBestScore => row.push(candidate_best.map(|(_, score)| score).into()),
+                Column::NCandidates => {
+                    let n_candidates = extra.and_then(|extra| extra.candidates.as_ref()).map(Vec::len);
+                    row.push(n_candidates.map(|n| n as u64).into())
+                }
+                Column::ExtraColumns => {
+                    for column in &extra_columns {
+                        let field = extra.and_then(|extra| {
+                            extra
+                                .other_columns
+                                .iter()
+                                .find(|(name, _)| *name == column.name)
+                                .map(|(_, field)| field.as_str())
+                        });
+                        row.extend(column.values(field));
+                    }
+                }
+                Column::BehaviouralMeasures => {
+                    for measure in &config.behavioural_measures {
+                        row.push(measure_value(&behaviour_measures, measure).into());
+                    }
+                }
+                Column::PhenotypeMeasures => {
+                    for measure in &config.phenotype_measures {
+                        row.push(measure_value(&phenotype_measures, measure).into());
                     }
                 }
+            }
+        }
+        all_measures.push(row);
+    }
+    behaviour_mismatches.report(issues)?;
+    phenotype_mismatches.report(issues)?;
+    quality.check_robots(&checked_robots, id_gen_species_map.keys(), phylogeny);
+    quality.count_nulls(&all_measures);
 
-                writeln!(&mut file_summary).unwrap();
-            },
-        );
+    if let Some(name) = &config.outputs.all_measures {
+        write_table(
+            &all_measures,
+            run_paths.output_file(name),
+            &config.outputs.formats,
+            &config.null_token,
+        )?;
+    }
 
-    Ok(())
+    if let Some(name) = &config.outputs.missing_measures {
+        write_table(
+            &missing_measures_table,
+            run_paths.output_file(name),
+            &config.outputs.formats,
+            &config.null_token,
+        )?;
+    }
+
+    if let Some(name) = &config.outputs.phylogeny {
+        write_table(
+            &phylogeny_table,
+            run_paths.phylogeny_output_file(name),
+            &config.outputs.formats,
+            &config.null_token,
+        )?;
+    }
+
+    let phylogeny_graph = PhylogenyGraph::new(phylogeny_nodes, phylogeny);
+    if let Some(name) = &config.outputs.phylogeny_graphml {
+        phylogeny_graph.write_graphml(run_paths.phylogeny_output_file(format!("{}.graphml", name)))?;
+    }
+    if let Some(name) = &config.outputs.phylogeny_dot {
+        phylogeny_graph.write_dot(run_paths.phylogeny_output_file(format!("{}.dot", name)))?;
+    }
+    if let Some(name) = &config.outputs.phylogeny_newick {
+        // not every phylogeny is a tree, the other outputs of the run are still valid
+        let newick_path = run_paths.phylogeny_output_file(format!("{}.nwk", name));
+        if let Err(e) = phylogeny_graph.write_newick(&newick_path) {
+            eprintln!("Warning: {}, {} not written", e, newick_path.display());
+            // a tree from a previous consolidation would not match the other outputs
+            let _ = fs::remove_file(&newick_path);
+        }
+    }
+
+    Ok((all_measures, fitnesses.into_iter().collect()))
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// Reads the `robot_id,fitness` lines of the fitness file, a fitness that is not a number (e.g. `None`) is missing
+/// Malformed lines are skipped and go through `issues`, like fitness values that are neither numbers nor `None`.
+fn load_fitness(path: &Path, issues: &mut Issues) -> Result<Vec<(u64, Option<f64>)>, Error> {
+    let file = fs::File::open(path).map_err(|e| Error::io(path, e))?;
+    let mut fitnesses = Vec::new();
+    for (line_number, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| Error::io(path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut line_split = line.split(',');
+        let (robot_id, fitness) = match (line_split.next(), line_split.next(), line_split.next()) {
+            (Some(robot_id), Some(fitness), None) => (robot_id, fitness),
+            _ => {
+                let message = format!("expected `robot_id,fitness`, found {:?}", line);
+                issues.tolerate(Error::malformed_line(path, line_number + 1, None, message))?;
+                continue;
+            }
+        };
+        let robot_id = match robot_id.trim().parse::<u64>() {
+            Ok(robot_id) => robot_id,
+            Err(_) => {
+                let message = format!("invalid id {:?}", robot_id);
+                issues.tolerate(Error::malformed_line(path, line_number + 1, Some("robot_id"), message))?;
+                continue;
+            }
+        };
+        let fitness = match fitness.trim().parse::<f64>() {
+            Ok(fitness) => Some(fitness),
+            Err(_) if fitness.trim() == "None" => None,
+            Err(_) => {
+                let message = format!("invalid value {:?}", fitness);
+                issues.tolerate(Error::malformed_line(path, line_number + 1, Some("fitness"), message))?;
+                None
+            }
+        };
+        fitnesses.push((robot_id, fitness));
+    }
+    Ok(fitnesses)
+}
+
+fn measure_value(descriptor: &Option<Descriptor>, measure: &str) -> Option<f64> {
+    descriptor
+        .as_ref()
+        .and_then(|descriptor| descriptor.get(measure).copied().flatten())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 struct SpeciesAge {
     evaluations: u64,
     generations: u64,
@@ -347,264 +372,849 @@ struct Species {
 
 impl Species {
     pub fn parse_from_file<P: AsRef<Path>>(path: &P) -> Result<Self, Error> {
-        let species_str = load_yaml_to_str(path)
-            .into_error("read yaml file failed")?;
-        let species: Self = serde_yaml::from_str(&species_str)
-            .into_error("parse yaml file failed")?;
+        let path = path.as_ref();
+        let species_str = load_yaml_to_str(&path)
+            .map_err(|e| Error::io(path, e))?;
+        let species: Self = serde_yaml::from_str(&species_str).map_err(|e| {
+            let line = e.location().map(|location| location.line()).unwrap_or(1);
+            Error::malformed_line(path, line, None, e)
+        })?;
         Ok(species)
     }
 }
 
-fn generate_shaphot_ids<P: AsRef<Path>>(run_path: &P) -> HashMap<u64, (Vec<(u64, u64, Vector2<f64>, Vector2<f64>)>, Option<CosituatedData>)> {
+/// A robot in the snapshot of a generation
+#[derive(Debug)]
+struct Snapshot {
+    generation: u64,
+    /// None without species files
+    species: Option<u64>,
+    species_age: Option<SpeciesAge>,
+    /// The row of the robot in the `extra.tsv` of the generation
+    extra: Option<CosituatedData>,
+}
+
+/// For each robot id: every snapshot it appears in
+type SnapshotMap = HashMap<u64, Vec<Snapshot>>;
+
+fn generate_shaphot_ids(
+    run_paths: &RunPaths,
+    config: &ExperimentConfig,
+    issues: &mut Issues,
+    quality: &mut QualityReport,
+) -> Result<SnapshotMap, Error> {
     // Generation, robot_id
     println!(
         "Generating snaphost_ids for {}",
-        run_path.as_ref().display()
+        run_paths.input.display()
     );
 
-    let ids_filepath = run_path.as_ref().join("snapshots_ids.tsv");
-    let mut ids_file = fs::File::create(ids_filepath).expect("could not create snapshot_ids file");
-    write!(&mut ids_file, "generation\trobot_id\tspecies_id\n").unwrap();
+    let mut ids_table = Table::new(snapshots_ids_columns());
+    let mut species_table = Table::new(species_columns());
+    let mut candidates_table = Table::new(candidates_columns());
     lazy_static! {
         static ref GENERATION_REGEX: Regex = Regex::new(r"^generation_(\d+)$").unwrap();
     }
 
-    let generations_path = run_path.as_ref().join("generations");
-    //TODO return optional species
-    let mut generated_ids_map: HashMap<u64, (Vec<(u64, u64, Vector2<f64>, Vector2<f64>)>, Option<CosituatedData>)> = HashMap::new();
+    let generations_path = run_paths.generations();
+    let mut generated_ids_map: SnapshotMap = HashMap::new();
 
-    for path in fs::read_dir(&generations_path).unwrap() {
-        let path = path.unwrap();
+    let mut generations = Vec::new();
+    let dir_reader = fs::read_dir(&generations_path).map_err(|e| Error::io(&generations_path, e))?;
+    for path in dir_reader {
+        let path = path.map_err(|e| Error::io(&generations_path, e))?;
         let filename = path.file_name();
         let generation_folder_name = filename.to_str().unwrap_or("");
         if let Some(gen_num_str) = GENERATION_REGEX.captures(generation_folder_name) {
             let captured_str = &gen_num_str[1]; // 0 is the whole string, 1 is the first match
-            let gen_num = captured_str.parse::<u64>().unwrap();
-
-            let generation_path = generations_path.join(generation_folder_name);
-            let ids_filename = generation_path.join("identifiers.txt");
-            let extra_filename = generation_path.join("extra.tsv");
-            let mut extra_data = match load_extra_cosituated_data(extra_filename) {
-                Ok(d) => d,
-                Err(Error {message: m, source_error: Some(e) }) => {
-                    if let Some(file_error) = e.downcast_ref::<std::io::Error>() {
-                        eprintln!("File error while opening extra data: {} => {:?}", m, file_error);
-                        Default::default()
-                    } else {
-                        eprintln!("ERROR IN EXTRA DATA PARSING {:?} -> {}\n{:?}", path, m, e);
-                        panic!("fail");
-                    }
+            let gen_num = captured_str
+                .parse::<u64>()
+                .into_error(format!("invalid generation number in {}", generation_folder_name))?;
+            generations.push((gen_num, path.path()));
+        } else {
+            println!("unread folder {}", generation_folder_name);
+        }
+    }
+    generations.sort_unstable();
+
+    let layout = match config.layout.snapshots {
+        SnapshotLayout::Auto => detect_snapshot_layout(generations.iter().map(|(_, path)| path.as_path()))?,
+        layout => layout,
+    };
+
+    for (gen_num, generation_path) in generations {
+        let extra_filename = run_paths.extra(&generation_path);
+        quality.generations += 1;
+        let mut extra_data = match load_extra_cosituated_data(&extra_filename, issues) {
+            Ok(d) => d,
+            Err(e) => {
+                if let Error::MissingFile { .. } = e {
+                    quality.generations_without_extra.push(gen_num);
                 }
-                Err(e) => {
-                    eprintln!("Error opening extra data: {:?}", e);
-                    panic!("fail");
-                },
-            };
-            let file = fs::File::open(ids_filename)
-                .expect("Could not open identifiers.txt file");
-
-            for line in io::BufReader::new(file).lines() {
-                let individual_id: u64 = line.unwrap().parse::<u64>().unwrap();
-                let (pos_start, pos_end): (Vector2<f64>, Vector2<f64>) = match extra_data
-                    .entry(individual_id) {
-                    Entry::Occupied(entry) => (entry.get().initial_position.clone(), entry.get().final_position.clone()),
-                    Entry::Vacant(_) => Default::default()
-                };
-                generated_ids_map
-                    .entry(individual_id)
-                    .or_default()
-                    .0
-                    .push((gen_num, 0, pos_start, pos_end));
-                write!(
-                    &mut ids_file,
-                    "{}\t{}\t{}\n",
-                    gen_num, individual_id, 0
-                )
-                    .unwrap();
+                issues.tolerate(e)?;
+                Default::default()
             }
+        };
+
+        let members: Vec<(u64, Option<(u64, SpeciesAge)>)> = match layout {
+            SnapshotLayout::Species => load_species(&generation_path, issues)?
+                .into_iter()
+                .flat_map(|species| {
+                    let (species_id, age) = (species.id, species.age);
+                    species_table.push(vec![
+                        Value::UInt(gen_num),
+                        Value::UInt(species_id),
+                        Value::UInt(species.individuals_ids.len() as u64),
+                        Value::UInt(age.evaluations),
+                        Value::UInt(age.generations),
+                        Value::UInt(age.no_improvements),
+                    ]);
+                    species
+                        .individuals_ids
+                        .into_iter()
+                        .map(move |individual_id| (individual_id, Some((species_id, age))))
+                })
+                .collect(),
+            _ => load_identifiers(&run_paths.identifiers(&generation_path), issues)?
+                .into_iter()
+                .map(|individual_id| (individual_id, None))
+                .collect(),
+        };
+
+        for (individual_id, species) in members {
+            let extra = extra_data.remove(&individual_id);
+            if let Some(candidates) = extra.as_ref().and_then(|extra| extra.candidates.as_ref()) {
+                for (candidate, score) in candidates {
+                    candidates_table.push(vec![
+                        Value::UInt(gen_num),
+                        Value::UInt(individual_id),
+                        Value::UInt(*candidate as u64),
+                        Value::Float(*score),
+                    ]);
+                }
+            }
+            generated_ids_map
+                .entry(individual_id)
+                .or_default()
+                .push(Snapshot {
+                    generation: gen_num,
+                    species: species.map(|(species_id, _)| species_id),
+                    species_age: species.map(|(_, age)| age),
+                    extra,
+                });
+            ids_table.push(vec![
+                Value::UInt(gen_num),
+                Value::UInt(individual_id),
+                species.map(|(species_id, _)| species_id).into(),
+            ]);
+        }
+    }
+
+    if let Some(name) = &config.outputs.snapshots_ids {
+        write_table(
+            &ids_table,
+            run_paths.output_file(name),
+            &config.outputs.formats,
+            &config.null_token,
+        )?;
+    }
+
+    if let Some(name) = &config.outputs.species {
+        // only runs with species files have species
+        if species_table.rows.is_empty() {
+            remove_table(run_paths.output_file(name), &config.outputs.formats)?;
         } else {
-            println!("unread folder {}", generation_folder_name);
+            write_table(&species_table, run_paths.output_file(name), &config.outputs.formats, &config.null_token)?;
+        }
+    }
+    if let Some(name) = &config.outputs.candidates {
+        // only runs with a `candidates` column in `extra.tsv` have candidates
+        if candidates_table.rows.is_empty() {
+            remove_table(run_paths.output_file(name), &config.outputs.formats)?;
+        } else {
+            write_table(&candidates_table, run_paths.output_file(name), &config.outputs.formats, &config.null_token)?;
         }
     }
 
-    generated_ids_map
+    Ok(generated_ids_map)
 }
 
-fn generate_shaphot_ids_generations_species<P: AsRef<Path>>(run_path: &P) -> HashMap<u64, Vec<(u64, u64)>> {
-    // Generation, robot_id
-    println!(
-        "Generating snaphost_ids for {}",
-        run_path.as_ref().display()
-    );
-
-    let ids_filepath = run_path.as_ref().join("snapshots_ids.tsv");
-    let mut ids_file = fs::File::create(ids_filepath).expect("could not create snapshot_ids file");
-    write!(&mut ids_file, "generation\trobot_id\tspecies_id\n").unwrap();
-    lazy_static! {
-        //static ref POP_FOLDER_REGEX: Regex = Regex::new(r"^selectedpop_(\d+)$").unwrap();
-        static ref GENERATION_REGEX: Regex = Regex::new(r"^generation_(\d+)$").unwrap();
-        static ref SPECIES_FILE_REGEX: Regex = Regex::new(r"^species_(\d+).yaml$").unwrap();
+/// Writes the species abundance per generation and the species lineage, only for runs with species files
+fn generate_species_dynamics(
+    run_paths: &RunPaths,
+    config: &ExperimentConfig,
+    id_gen_species_map: &SnapshotMap,
+    phylogeny: &HashMap<u64, Vec<u64>>,
+) -> Result<(), Error> {
+    let members = id_gen_species_map.iter().flat_map(|(robot_id, snapshots)| {
+        snapshots
+            .iter()
+            .filter_map(move |snapshot| snapshot.species.map(|species| (*robot_id, snapshot.generation, species)))
+    });
+    let has_species = members.clone().next().is_some();
+    let dynamics = SpeciesDynamics::new(members, phylogeny);
+    for (name, table) in [
+        (&config.outputs.muller_population, dynamics.population_table()),
+        (&config.outputs.muller_edges, dynamics.edges_table()),
+        (&config.outputs.species_lifespans, dynamics.lifespans_table()),
+    ] {
+        match name {
+            Some(name) if has_species => {
+                write_table(&table, run_paths.output_file(name), &config.outputs.formats, &config.null_token)?
+            }
+            Some(name) => remove_table(run_paths.output_file(name), &config.outputs.formats)?,
+            None => {}
+        }
     }
+    Ok(())
+}
 
-    let generations_path = run_path.as_ref().join("generations");
-    let mut generated_ids_map: HashMap<u64, Vec<(u64, u64)>> = HashMap::new();
+lazy_static! {
+    static ref SPECIES_FILE_REGEX: Regex = Regex::new(r"^species_(\d+).yaml$").unwrap();
+}
 
-    for path in fs::read_dir(&generations_path).unwrap() {
-        let path = path.unwrap();
-        let filename = path.file_name();
-        let filename = filename.to_str().unwrap_or("");
-        if let Some(gen_num_str) = GENERATION_REGEX.captures(filename) {
-            let captured_str = &gen_num_str[1]; // 0 is the whole string, 1 is the first match
-            let gen_num = captured_str.parse::<u64>().unwrap();
-
-            let species_path = generations_path.join(filename);
-            for species_file in fs::read_dir(species_path).unwrap() {
-                let species_file = species_file.unwrap();
-                let species_filename = species_file.file_name();
-                let species_filename = species_filename.to_str().unwrap_or("");
-                if let Some(species_filename_regex_match) =
-                    SPECIES_FILE_REGEX.captures(species_filename)
-                {
-                    let species_num_from_filename =
-                        species_filename_regex_match[1].parse::<u64>().unwrap();
-
-                    let species = Species::parse_from_file(&species_file.path())
-                        .expect("could not read phylogeny file for individual");
-
-                    assert_eq!(species.id, species_num_from_filename);
-                    for individual_id in species.individuals_ids {
-                        generated_ids_map
-                            .entry(individual_id)
-                            .or_default()
-                            .push((gen_num, species.id));
-                        write!(
-                            &mut ids_file,
-                            "{}\t{}\t{}\n",
-                            gen_num, individual_id, species.id
-                        )
-                        .unwrap();
-                    }
-                }
+/// `Species` if any of the generation folders has species files, `Identifiers` otherwise
+fn detect_snapshot_layout<'a, I>(generation_paths: I) -> Result<SnapshotLayout, Error>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    for generation_path in generation_paths {
+        let dir_reader = fs::read_dir(generation_path).map_err(|e| Error::io(generation_path, e))?;
+        for entry in dir_reader {
+            let entry = entry.map_err(|e| Error::io(generation_path, e))?;
+            if SPECIES_FILE_REGEX.is_match(entry.file_name().to_str().unwrap_or("")) {
+                return Ok(SnapshotLayout::Species);
             }
-        } else {
-            println!("unread folder {}", filename);
         }
     }
+    Ok(SnapshotLayout::Identifiers)
+}
 
-    generated_ids_map
+/// Reads the robot ids of a generation, one per line. Malformed lines are skipped and go through `issues`.
+fn load_identifiers(ids_filename: &Path, issues: &mut Issues) -> Result<Vec<u64>, Error> {
+    let file = fs::File::open(ids_filename).map_err(|e| Error::io(ids_filename, e))?;
+    let mut ids = Vec::new();
+    for (line_number, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| Error::io(ids_filename, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.trim().parse::<u64>() {
+            Ok(individual_id) => ids.push(individual_id),
+            Err(_) => {
+                let message = format!("invalid robot id {:?}", line);
+                issues.tolerate(Error::malformed_line(ids_filename, line_number + 1, None, message))?;
+            }
+        }
+    }
+    Ok(ids)
 }
 
+/// Reads the `species_<id>.yaml` files of a generation, sorted by species id.
+/// Unreadable files and files whose id does not match their name go through `issues`.
+fn load_species(generation_path: &Path, issues: &mut Issues) -> Result<Vec<Species>, Error> {
+    let mut all_species = Vec::new();
+    let dir_reader = fs::read_dir(generation_path).map_err(|e| Error::io(generation_path, e))?;
+    for species_file in dir_reader {
+        let species_file = species_file.map_err(|e| Error::io(generation_path, e))?;
+        let species_filename = species_file.file_name();
+        let species_filename = species_filename.to_str().unwrap_or("");
+        let species_num_from_filename = match SPECIES_FILE_REGEX
+            .captures(species_filename)
+            .and_then(|species_filename_regex_match| species_filename_regex_match[1].parse::<u64>().ok())
+        {
+            Some(species_num) => species_num,
+            None => continue,
+        };
+
+        let species = match Species::parse_from_file(&species_file.path()) {
+            Ok(species) => species,
+            Err(e) => {
+                issues.tolerate(e)?;
+                continue;
+            }
+        };
+        if species.id != species_num_from_filename {
+            let message = format!("species id {} does not match the file name", species.id);
+            issues.tolerate(Error::inconsistent_data(species_file.path(), message))?;
+            continue;
+        }
+        all_species.push(species);
+    }
+    all_species.sort_unstable_by_key(|species| species.id);
+    Ok(all_species)
+}
+
+/// Row of a robot in the `extra.tsv` of a generation
 #[derive(Debug)]
 struct CosituatedData {
     pub initial_position: Vector2<f64>,
     pub final_position: Vector2<f64>,
-    pub candidate_best: (usize, f64),
-    pub candidates: Vec<(usize, f64)>,
+    /// Index and score of the best candidate, None without a `candidate_best` column or if the robot has none
+    pub candidate_best: Option<(usize, f64)>,
+    /// Index and score of every candidate, None without a `candidates` column or if the robot has none
+    pub candidates: Option<Vec<(usize, f64)>>,
+    /// Name and text of the fields of all the other columns
+    pub other_columns: Vec<(String, String)>,
 }
 
-fn load_extra_cosituated_data<P: AsRef<Path>>(filename: P) -> Result<HashMap<u64, CosituatedData>, Error>
-{
-    let file = fs::File::open(filename).into_error("couldn't open extra file")?;
-    io::BufReader::new(file)
-        .lines()
-        .skip(1) // skip header
-        .map(|line| {
-            let line = line.into_error("Reading line error")?;
-            let mut split = line.split('\t');
-            let id = split.next().unwrap().parse::<u64>().into_error("parsing robot id error")?;
-            let initial_position = Vector2::parse_from_python(split.next().unwrap())?;
-            let final_position = Vector2::parse_from_python(split.next().unwrap())?;
-            Ok((id, CosituatedData {
-                initial_position,
-                final_position,
-                candidate_best: (0, 0.0),
-                candidates: Vec::new(),
-            }))
-        }).collect()
+/// An `(index, score)` python tuple
+fn candidate_from_python(value: &PyValue) -> Result<(usize, f64), String> {
+    match value.as_sequence() {
+        Some([PyValue::Int(index), score]) if *index >= 0 => score
+            .as_f64()
+            .map(|score| (*index as usize, score))
+            .ok_or_else(|| format!("invalid score {}", score)),
+        _ => Err(format!("invalid candidate {}", value)),
+    }
+}
+
+/// Parses an `(index, score)` python tuple, `None` if the robot has no candidate
+fn parse_candidate(data: &str) -> Result<Option<(usize, f64)>, String> {
+    match PyValue::parse(data).map_err(|e| e.to_string())? {
+        PyValue::None => Ok(None),
+        value => candidate_from_python(&value).map(Some),
+    }
 }
 
+/// Parses a python list of `(index, score)` tuples, `None` if the robot has no candidates.
+/// `None` elements are missing candidates and are left out.
+fn parse_candidates(data: &str) -> Result<Option<Vec<(usize, f64)>>, String> {
+    match PyValue::parse(data).map_err(|e| e.to_string())? {
+        PyValue::None => Ok(None),
+        PyValue::List(values) => values
+            .iter()
+            .filter(|value| **value != PyValue::None)
+            .map(candidate_from_python)
+            .collect::<Result<_, _>>()
+            .map(Some),
+        value => Err(format!("invalid candidate list {}", value)),
+    }
+}
 
-fn load_phylogeny<P: AsRef<Path>>(path: &P) -> Result<HashMap<u64, Vec<u64>>, Error> {
-    let phylogeny_folder = path.as_ref().join("data_fullevolution").join("phylogeny");
+/// Reads the positions, candidates and other fields of the robots of a generation, the columns are found by the header.
+/// Malformed lines are skipped and go through `issues`, malformed candidates go through `issues` and are left out.
+fn load_extra_cosituated_data<P: AsRef<Path>>(filename: P, issues: &mut Issues) -> Result<HashMap<u64, CosituatedData>, Error>
+{
+    let filename = filename.as_ref();
+    let file = fs::File::open(filename).map_err(|e| Error::io(filename, e))?;
+    let mut lines = io::BufReader::new(file).lines().enumerate();
 
-    let dir_reader = fs::read_dir(&phylogeny_folder)
-        .into_error(format!("Could not open phylogeny folder ({})", phylogeny_folder.display()))?;
+    let header = match lines.next() {
+        Some((_, header)) => header.map_err(|e| Error::io(filename, e))?,
+        None => return Ok(HashMap::new()),
+    };
+    let header: Vec<&str> = header.split('\t').map(str::trim).collect();
+    let column_index = |column: &str| header.iter().position(|name| *name == column);
+    let required_column = |column: &str| {
+        column_index(column).ok_or_else(|| Error::malformed_line(filename, 1, Some(column), "missing column"))
+    };
+    let id_index = required_column("id")?;
+    let initial_position_index = required_column("initial_position")?;
+    let final_position_index = required_column("final_position")?;
+    let candidate_best_index = column_index("candidate_best");
+    let candidates_index = column_index("candidates");
+    let known_indices = [
+        Some(id_index),
+        Some(initial_position_index),
+        Some(final_position_index),
+        candidate_best_index,
+        candidates_index,
+    ];
+    let other_indices: Vec<usize> = (0..header.len())
+        .filter(|index| !known_indices.contains(&Some(*index)))
+        .collect();
 
-    dir_reader.filter_map(|phylogeny_file| {
-            let phylogeny_file = phylogeny_file.unwrap();
-            let filename = phylogeny_file.file_name();
-            let filename = filename.to_str().unwrap_or("");
-            lazy_static! {
-                static ref PHYLOGENY_FILE_REGEX: Regex =
-                    Regex::new(r"^parents_(\d+).yaml$").unwrap();
+    // problems in the optional candidate columns are returned with the row, instead of discarding it
+    let parse_line = |line_number: usize, line: &str| -> Result<(u64, CosituatedData, Vec<Error>), Error> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let malformed = |column: &str, message: String| {
+            Error::malformed_line(filename, line_number + 1, Some(column), message)
+        };
+        let field = |index: usize| fields.get(index).ok_or_else(|| malformed(header[index], "missing".to_string()));
+        let id = field(id_index)?;
+        let id = id.parse::<u64>().map_err(|_| malformed("id", format!("invalid robot id {:?}", id)))?;
+        let initial_position = Vector2::parse_from_python(field(initial_position_index)?)
+            .map_err(|e| malformed("initial_position", e.to_string()))?;
+        let final_position = Vector2::parse_from_python(field(final_position_index)?)
+            .map_err(|e| malformed("final_position", e.to_string()))?;
+        let optional_field = |index: usize| fields.get(index).copied().ok_or_else(|| "missing".to_string());
+        let mut problems = Vec::new();
+        let candidate_best = match candidate_best_index.map(|index| optional_field(index).and_then(parse_candidate)) {
+            Some(Ok(candidate_best)) => candidate_best,
+            Some(Err(e)) => {
+                problems.push(malformed("candidate_best", e));
+                None
             }
-            if let Some(robot_id) = PHYLOGENY_FILE_REGEX.captures(filename) {
-                robot_id[1]
-                    .parse::<u64>()
-                    .map(|robot_id| (robot_id, phylogeny_file))
-                    .ok()
-            } else {
+            None => None,
+        };
+        let candidates = match candidates_index.map(|index| optional_field(index).and_then(parse_candidates)) {
+            Some(Ok(candidates)) => candidates,
+            Some(Err(e)) => {
+                problems.push(malformed("candidates", e));
                 None
             }
-        })
-        .map(|(robot_id, phylogeny_file)| {
-            let mut robot_phylogeny_str = load_yaml_to_str(&phylogeny_file.path())
-                .expect("could not read phylogeny file for individual");
-            robot_phylogeny_str += "\n";
-            use yaml_rust::{Yaml, YamlLoader};
-            let parents: Vec<u64> = if robot_phylogeny_str == "parents: null\n" {
-                Vec::new()
-            } else {
-                use yaml_rust::{Yaml, YamlLoader};
-                let robot_phylogeny = YamlLoader::load_from_str(&robot_phylogeny_str)
-                    .into_error("Error loading yaml from string")?;
-                let parents: Vec<u64> = match &robot_phylogeny[0]["parents"] {
-                    Yaml::Array(array) => array
-                        .iter()
-                        .map(|node| node.as_i64().unwrap() as u64)
-                        .collect(),
-                    Yaml::Null => Vec::new(),
-                    Yaml::Real(_) => panic!("phylogeny parents yaml parse error: Real"),
-                    Yaml::Integer(single_parent) => vec![*single_parent as u64],
-                    Yaml::String(text) => text.split(",").map(|v| v.parse().unwrap()).collect(),
-                    Yaml::Boolean(_) => panic!("phylogeny parents yaml parse error: Boolean"),
-                    Yaml::Hash(_) => panic!("phylogeny parents yaml parse error: Hash"),
-                    Yaml::Alias(_) => panic!("phylogeny parents yaml parse error: Alias"),
-                    Yaml::BadValue => panic!("phylogeny parents yaml parse error: BadValue"),
+            None => None,
+        };
+        let other_columns = other_indices
+            .iter()
+            .filter_map(|index| fields.get(*index).map(|field| (header[*index].to_string(), field.to_string())))
+            .collect();
+        let data = CosituatedData {
+            initial_position,
+            final_position,
+            candidate_best,
+            candidates,
+            other_columns,
+        };
+        Ok((id, data, problems))
+    };
+
+    let mut extra_data = HashMap::new();
+    for (line_number, line) in lines {
+        let line = line.map_err(|e| Error::io(filename, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line_number, &line) {
+            Ok((id, data, problems)) => {
+                for problem in problems {
+                    issues.tolerate(problem)?;
+                }
+                extra_data.insert(id, data);
+            }
+            Err(e) => issues.tolerate(e)?,
+        }
+    }
+    Ok(extra_data)
+}
+
+/// Parents of every robot with a `parents_<id>.yaml` file.
+/// A missing phylogeny folder or unreadable parents files go through `issues`.
+fn load_phylogeny(run_paths: &RunPaths, issues: &mut Issues) -> Result<HashMap<u64, Vec<u64>>, Error> {
+    let phylogeny_folder = run_paths.phylogeny_folder();
+    lazy_static! {
+        static ref PHYLOGENY_FILE_REGEX: Regex =
+            Regex::new(r"^parents_(\d+).yaml$").unwrap();
+    }
+
+    let mut phylogeny = HashMap::new();
+    let dir_reader = match fs::read_dir(&phylogeny_folder) {
+        Ok(dir_reader) => dir_reader,
+        Err(e) => {
+            issues.tolerate(Error::io(&phylogeny_folder, e))?;
+            return Ok(phylogeny);
+        }
+    };
+    for phylogeny_file in dir_reader {
+        let phylogeny_file = phylogeny_file.map_err(|e| Error::io(&phylogeny_folder, e))?;
+        let filename = phylogeny_file.file_name();
+        let filename = filename.to_str().unwrap_or("");
+        let robot_id = match PHYLOGENY_FILE_REGEX
+            .captures(filename)
+            .and_then(|robot_id| robot_id[1].parse::<u64>().ok())
+        {
+            Some(robot_id) => robot_id,
+            None => continue,
+        };
+        match load_parents(&phylogeny_file.path()) {
+            Ok(parents) => {
+                phylogeny.insert(robot_id, parents);
+            }
+            Err(e) => issues.tolerate(e)?,
+        }
+    }
+    Ok(phylogeny)
+}
+
+/// Reads the parent ids from a `parents_<id>.yaml` file
+fn load_parents(phylogeny_path: &Path) -> Result<Vec<u64>, Error> {
+    let robot_phylogeny_str = fs::read_to_string(phylogeny_path).map_err(|e| Error::io(phylogeny_path, e))?;
+    yaml::parse_parents(phylogeny_path, &robot_phylogeny_str)
+}
+
+/// Everything a worker needs to consolidate a run
+struct RunJob {
+    id: RunId,
+    paths: RunPaths,
+    config: Arc<ExperimentConfig>,
+    discover_measures: bool,
+    sqlite: Option<Arc<SqliteDatabase>>,
+    strictness: Strictness,
+    /// Consolidate even if the manifest says the outputs are up to date
+    force: bool,
+    /// Return the all_measures table, for the combined datasets
+    keep_table: bool,
+}
+
+/// What a worker did with a run
+struct RunOutcome {
+    status: RunStatus,
+    generations: usize,
+    robots: usize,
+    /// Only if `keep_table` was set in the job
+    all_measures: Option<Table>,
+}
+
+/// Consolidates the run, unless its outputs are up to date, and returns its all_measures table if requested
+fn analyze(job: &RunJob) -> Result<RunOutcome, Error> {
+    let RunJob { id, paths: run_paths, .. } = job;
+    let options = format!(
+        "discover_measures={} sqlite={:?} strictness={:?}",
+        job.discover_measures,
+        job.sqlite.as_ref().map(|sqlite| sqlite.path()),
+        job.strictness
+    );
+    let report_issues = job.strictness == Strictness::Report;
+    let mut manifest = Manifest::build(run_paths, &job.config, &options, report_issues)?;
+    if !job.force {
+        if let Some(previous) = Manifest::load(run_paths) {
+            // the database can be deleted or replaced without touching the outputs of the run
+            let in_database = match &job.sqlite {
+                Some(sqlite) => sqlite.has_run(&id.experiment, &id.run)?,
+                None => true,
+            };
+            if previous.is_up_to_date(&manifest, run_paths) && in_database {
+                println!("Skipping {}, run {}: up to date", id.experiment, id.run);
+                let all_measures = match (&job.config.outputs.all_measures, job.keep_table) {
+                    (Some(name), true) => Some(read_table(
+                        run_paths.output_file(name),
+                        &job.config.outputs.formats,
+                        &job.config.null_token,
+                        &job.config.all_measures_columns(&[]),
+                    )?),
+                    _ => None,
                 };
+                return Ok(RunOutcome {
+                    status: RunStatus::Skipped,
+                    generations: 0,
+                    robots: 0,
+                    all_measures,
+                });
+            }
+        }
+    }
+
+    println!("Consilidating {}, run {} ... ", id.experiment, id.run);
+    run_paths.create_output_dir().into_error("could not create output folder")?;
+    let discovered_config;
+    let config = if job.discover_measures {
+        discovered_config = DiscoveredMeasures::scan(run_paths)?.apply_to(&job.config);
+        &discovered_config
+    } else {
+        &job.config
+    };
+    let mut issues = Issues::new(job.strictness);
+    let phylogeny = load_phylogeny(run_paths, &mut issues)?;
+    let mut quality = QualityReport::default();
+    let id_gen_species_map = generate_shaphot_ids(run_paths, config, &mut issues, &mut quality)?;
+    generate_species_dynamics(run_paths, config, &id_gen_species_map, &phylogeny)?;
+
+    let mut records = job.sqlite.as_ref().map(|_| RunRecords::default());
+    let (all_measures, fitness) = generate_all_measures(
+        run_paths,
+        config,
+        &id_gen_species_map,
+        &phylogeny,
+        records.as_mut(),
+        &mut issues,
+        &mut quality,
+    )?;
+    if let Some(name) = &config.outputs.lineage {
+        let lineage = lineage_table(&all_measures, &fitness, &phylogeny, &config.phenotype_measures, config.lineage_best);
+        write_table(&lineage, run_paths.output_file(name), &config.outputs.formats, &config.null_token)?;
+    }
+    let (generations, robots) = (quality.generations, quality.robots);
+    if let Some(name) = &config.outputs.quality_report {
+        quality.write(run_paths.output_file(format!("{}.json", name)))?;
+    }
+    if let Some(name) = &config.outputs.issues {
+        issues.write_report(run_paths.output_file(name), &config.outputs.formats, &config.null_token)?;
+    }
+
+    if let (Some(sqlite), Some(mut records)) = (&job.sqlite, records) {
+        for (robot_id, snapshots) in &id_gen_species_map {
+            records.snapshots.extend(
+                snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.generation, *robot_id, snapshot.species)),
+            );
+        }
+        records.snapshots.sort_unstable();
+        for (child_id, parents) in &phylogeny {
+            records.parents.extend(
                 parents
-            };
+                    .iter()
+                    .enumerate()
+                    .map(|(i, parent_id)| (*child_id, *parent_id, i + 1)),
+            );
+        }
+        records.parents.sort_unstable();
+        sqlite.insert_run(&id.experiment, &id.run, &records)?;
+    }
 
-            Ok((robot_id, parents))
-        })
-        .collect()
+    manifest.retain_written_outputs(run_paths);
+    manifest.save(run_paths)?;
+    Ok(RunOutcome {
+        status: RunStatus::Succeeded,
+        generations,
+        robots,
+        all_measures: Some(all_measures).filter(|_| job.keep_table),
+    })
 }
 
-fn analyze(exp: &str, run: u16) -> Result<(), Error> {
-    println!("Consilidating {}, run {} ... ", exp, run);
-    let run_path = Path::new(DIR_PATH).join(exp).join(run.to_string());
-    let phylogeny = load_phylogeny(&run_path)?;
-    let id_gen_species_map = generate_shaphot_ids(&run_path);
-    generate_all_measures(&run_path, &id_gen_species_map, &phylogeny)
+/// Writes the all_measures tables of the runs merged in one file per experiment,
+/// and in a single file for the whole data root if `whole_root` is set
+fn write_combined_datasets(
+    tables: &[(RunId, Table)],
+    arguments: &Arguments,
+    config: &Config,
+    whole_root: bool,
+) -> Result<(), Error> {
+    let output_root = arguments.output_root();
+
+    let mut experiments: Vec<&str> = tables.iter().map(|(id, _)| id.experiment.as_str()).collect();
+    experiments.sort_unstable();
+    experiments.dedup();
+
+    for experiment in experiments {
+        let experiment_config = config.experiment(experiment);
+        let name = match &experiment_config.outputs.all_measures {
+            Some(name) => name,
+            None => continue,
+        };
+        println!("Combining the runs of {}", experiment);
+        let combined = Table::combine(
+            tables
+                .iter()
+                .filter(|(id, _)| id.experiment == experiment)
+                .map(|(id, table)| (id.experiment.as_str(), id.run.as_str(), table)),
+        );
+        let experiment_folder = output_root.join(experiment);
+        fs::create_dir_all(&experiment_folder)
+            .into_error(format!("could not create folder {}", experiment_folder.display()))?;
+        write_table(
+            &combined,
+            experiment_folder.join(name),
+            &experiment_config.outputs.formats,
+            &experiment_config.null_token,
+        )?;
+    }
+
+    if whole_root {
+        let root_config = config.default_experiment();
+        if let Some(name) = &root_config.outputs.all_measures {
+            println!("Combining all the runs");
+            let combined = Table::combine(
+                tables
+                    .iter()
+                    .map(|(id, table)| (id.experiment.as_str(), id.run.as_str(), table)),
+            );
+            fs::create_dir_all(output_root)
+                .into_error(format!("could not create folder {}", output_root.display()))?;
+            write_table(
+                &combined,
+                output_root.join(name),
+                &root_config.outputs.formats,
+                &root_config.null_token,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Scans the descriptors of all the runs and returns, for each experiment,
+/// its configuration extended with the measures found in any of its runs
+fn discover_experiment_measures(
+    pool: &ThreadPool,
+    runs: &[RunId],
+    arguments: &Arguments,
+    config: &Config,
+) -> HashMap<String, Arc<ExperimentConfig>> {
+    println!("Discovering measures in {} runs", runs.len());
+    let (sender, receiver) = mpsc::channel();
+    for run_id in runs {
+        let experiment = run_id.experiment.clone();
+        let run_paths = RunPaths::new(
+            &arguments.data_root,
+            arguments.output_dir.as_ref(),
+            &run_id.experiment,
+            &run_id.run,
+            &config.experiment(&run_id.experiment).layout,
+        );
+        let sender = sender.clone();
+        pool.execute(move || {
+            match DiscoveredMeasures::scan(&run_paths) {
+                Ok(measures) => sender.send((experiment, measures)).unwrap(),
+                Err(e) => eprintln!("Could not discover measures in {}: {}", run_paths.input.display(), e),
+            }
+        });
+    }
+    drop(sender);
+
+    let mut discovered: HashMap<String, DiscoveredMeasures> = HashMap::new();
+    for (experiment, measures) in receiver {
+        discovered.entry(experiment).or_default().merge(measures);
+    }
+    discovered
+        .into_iter()
+        .map(|(experiment, measures)| {
+            let experiment_config = measures.apply_to(&config.experiment(&experiment));
+            (experiment, Arc::new(experiment_config))
+        })
+        .collect()
 }
 
 fn main() {
-    if let Ok(path) = std::env::current_dir() {
-        println!("Consolidating experiments in folder {:?}", path);
-    }
-    let n_workers = num_cpus::get();
-    let pool = ThreadPool::new(n_workers);
-
-    for exp in EXPERIMENT_TYPES {
-        for run in RUNS {
-            pool.execute(move || {
-                let r = analyze(exp, run);
-                if r.is_err() {
-                    println!("{}:{} failed because {:?}", exp, run, r);
-                }
-            });
+    let arguments = Arguments::parse_and_validate();
+    if arguments.print_default_config {
+        print!("{}", Config::default_yaml());
+        return;
+    }
+
+    let mut config = match &arguments.config {
+        Some(config_path) => match Config::load(config_path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Could not load configuration: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+    if !arguments.formats.is_empty() {
+        config.override_formats(&arguments.formats);
+    }
+
+    let batch_start = Instant::now();
+    println!("Consolidating experiments in folder {:?}", arguments.data_root);
+    let pool = ThreadPool::new(arguments.workers());
+
+    let runs = match discover_runs(&arguments.data_root, &arguments.run_filter(), &config) {
+        Ok(runs) => runs,
+        Err(e) => {
+            eprintln!("Could not look for runs in {}: {}", arguments.data_root.display(), e);
+            std::process::exit(1);
         }
+    };
+    println!("Found {} runs to consolidate", runs.len());
+
+    let experiment_configs: HashMap<String, Arc<ExperimentConfig>> =
+        if arguments.discover_measures == Some(DiscoveryScope::All) {
+            discover_experiment_measures(&pool, &runs, &arguments, &config)
+        } else {
+            HashMap::new()
+        };
+    let discover_run_measures = arguments.discover_measures == Some(DiscoveryScope::Run);
+
+    let mut databases: HashMap<PathBuf, Arc<SqliteDatabase>> = HashMap::new();
+    let combine = arguments.combine || arguments.combine_root;
+    let (outcome_sender, outcome_receiver) = mpsc::channel();
+
+    for run_id in runs {
+        let experiment_config: Arc<ExperimentConfig> = experiment_configs
+            .get(&run_id.experiment)
+            .cloned()
+            .unwrap_or_else(|| config.experiment(&run_id.experiment));
+        let run_paths = RunPaths::new(
+            &arguments.data_root,
+            arguments.output_dir.as_ref(),
+            &run_id.experiment,
+            &run_id.run,
+            &experiment_config.layout,
+        );
+
+        let sqlite = match arguments.sqlite {
+            Some(scope) => {
+                let database_path = scope.database_path(arguments.output_root(), &run_id.experiment);
+                let database = match databases.entry(database_path) {
+                    Entry::Occupied(entry) => entry.get().clone(),
+                    Entry::Vacant(entry) => match SqliteDatabase::open(entry.key()) {
+                        Ok(database) => entry.insert(Arc::new(database)).clone(),
+                        Err(e) => {
+                            eprintln!("Could not open database {}: {}", entry.key().display(), e);
+                            std::process::exit(1);
+                        }
+                    },
+                };
+                Some(database)
+            }
+            None => None,
+        };
+
+        let job = RunJob {
+            id: run_id,
+            paths: run_paths,
+            config: experiment_config,
+            discover_measures: discover_run_measures,
+            sqlite,
+            strictness: arguments.strictness,
+            force: arguments.force,
+            keep_table: combine,
+        };
+        let outcome_sender = outcome_sender.clone();
+        pool.execute(move || {
+            let start = Instant::now();
+            // a panicking run is a failed run, it must still be in the summary and the exit code
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| analyze(&job))).unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(Error::new(format!("panicked: {}", message)))
+            });
+            if let Err(e) = &outcome {
+                println!("{}:{} failed because {}", job.id.experiment, job.id.run, e);
+            }
+            outcome_sender.send((job.id, outcome, start.elapsed())).unwrap();
+        });
     }
+    drop(outcome_sender);
 
+    let mut summary = BatchSummary::default();
+    let mut tables: Vec<(RunId, Table)> = Vec::new();
+    for (run_id, outcome, elapsed) in outcome_receiver {
+        match outcome {
+            Ok(outcome) => {
+                summary.record(&run_id.experiment, outcome.status, outcome.generations, outcome.robots, elapsed);
+                if let Some(table) = outcome.all_measures {
+                    tables.push((run_id, table));
+                }
+            }
+            Err(_) => summary.record(&run_id.experiment, RunStatus::Failed, 0, 0, elapsed),
+        }
+    }
     pool.join();
+
+    let mut failed = false;
+    if combine {
+        tables.sort_by(|(a, _), (b, _)| {
+            a.experiment
+                .cmp(&b.experiment)
+                .then_with(|| compare_run_names(&a.run, &b.run))
+        });
+        if let Err(e) = write_combined_datasets(&tables, &arguments, &config, arguments.combine_root) {
+            eprintln!("Could not write the combined datasets: {}", e);
+            failed = true;
+        }
+    }
+
+    summary.finish(batch_start.elapsed());
+    summary.print();
+    let root_config = config.default_experiment();
+    if let Some(name) = &root_config.outputs.batch_summary {
+        let written = fs::create_dir_all(arguments.output_root())
+            .into_error(format!("could not create folder {}", arguments.output_root().display()))
+            .and_then(|_| summary.write(arguments.output_root().join(name), &root_config.null_token));
+        if let Err(e) = written {
+            eprintln!("Could not write the batch summary: {}", e);
+            failed = true;
+        }
+    }
+
+    if failed || summary.any_failed() {
+        std::process::exit(1);
+    }
 }
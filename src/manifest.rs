@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::config::ExperimentConfig;
+use crate::error::{ConvertResult, Error};
+use crate::output::with_extension;
+use crate::paths::RunPaths;
+
+const MANIFEST_FILENAME: &str = "manifest.yaml";
+
+/// Size and modification time of an input file, used to detect changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFile {
+    /// Relative to the run folder
+    pub path: String,
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+/// Record of what a consolidation of a run read and produced, stored next to the outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub tool_version: String,
+    /// Hash of the configuration and options the outputs were generated with
+    pub settings_hash: String,
+    pub inputs: Vec<InputFile>,
+    /// Relative to the output folder of the run
+    pub outputs: Vec<PathBuf>,
+}
+
+impl Manifest {
+    /// Fingerprints the current inputs of the run, and lists the outputs the configuration generates
+    pub fn build(run_paths: &RunPaths, config: &ExperimentConfig, options: &str, report_issues: bool) -> Result<Self, Error> {
+        let outputs = expected_outputs(run_paths, config, report_issues);
+        // when writing in place some outputs live in the input folders
+        let exclude: HashSet<&Path> = outputs.iter().map(PathBuf::as_path).collect();
+
+        let mut inputs = Vec::new();
+        for folder in [run_paths.generations(), run_paths.data_fullevolution()] {
+            scan_inputs(&run_paths.input, &folder, &exclude, &mut inputs)?;
+        }
+        inputs.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Manifest {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            settings_hash: settings_hash(config, options)?,
+            inputs,
+            outputs: outputs.iter().map(|output| run_paths.relative_output_file(output)).collect(),
+        })
+    }
+
+    pub fn path(run_paths: &RunPaths) -> PathBuf {
+        run_paths.output_file(MANIFEST_FILENAME)
+    }
+
+    /// Previous manifest of the run, if there is a readable one
+    pub fn load(run_paths: &RunPaths) -> Option<Self> {
+        let manifest_str = fs::read_to_string(Self::path(run_paths)).ok()?;
+        serde_yaml::from_str(&manifest_str).ok()
+    }
+
+    pub fn save(&self, run_paths: &RunPaths) -> Result<(), Error> {
+        let path = Self::path(run_paths);
+        let manifest_str = serde_yaml::to_string(self).into_error("could not serialize manifest")?;
+        fs::write(&path, manifest_str).into_error(format!("could not write manifest {}", path.display()))
+    }
+
+    /// Forgets the expected outputs the consolidation did not write, e.g. a Newick tree of a phylogeny with two parents
+    pub fn retain_written_outputs(&mut self, run_paths: &RunPaths) {
+        self.outputs.retain(|output| run_paths.resolve_output_file(output).is_file());
+    }
+
+    /// True if `current` describes the same inputs and settings as this manifest,
+    /// and all the outputs written then are still there
+    pub fn is_up_to_date(&self, current: &Manifest, run_paths: &RunPaths) -> bool {
+        self.tool_version == current.tool_version
+            && self.settings_hash == current.settings_hash
+            && self.inputs == current.inputs
+            && self.outputs.iter().all(|output| run_paths.resolve_output_file(output).is_file())
+    }
+}
+
+/// Files a consolidation of the run writes with this configuration
+fn expected_outputs(run_paths: &RunPaths, config: &ExperimentConfig, report_issues: bool) -> Vec<PathBuf> {
+    let mut outputs = Vec::new();
+    let with_formats = |outputs: &mut Vec<PathBuf>, stem: PathBuf| {
+        for format in &config.outputs.formats {
+            outputs.push(with_extension(&stem, format.extension()));
+        }
+    };
+    if let Some(name) = &config.outputs.all_measures {
+        with_formats(&mut outputs, run_paths.output_file(name));
+    }
+    if let Some(name) = &config.outputs.phylogeny {
+        with_formats(&mut outputs, run_paths.phylogeny_output_file(name));
+    }
+    for (name, extension) in [
+        (&config.outputs.phylogeny_graphml, "graphml"),
+        (&config.outputs.phylogeny_dot, "dot"),
+        (&config.outputs.phylogeny_newick, "nwk"),
+    ] {
+        if let Some(name) = name {
+            outputs.push(run_paths.phylogeny_output_file(format!("{}.{}", name, extension)));
+        }
+    }
+    if let Some(name) = &config.outputs.snapshots_ids {
+        with_formats(&mut outputs, run_paths.output_file(name));
+    }
+    if let Some(name) = &config.outputs.missing_measures {
+        with_formats(&mut outputs, run_paths.output_file(name));
+    }
+    for name in [
+        &config.outputs.species,
+        &config.outputs.muller_population,
+        &config.outputs.muller_edges,
+        &config.outputs.species_lifespans,
+        &config.outputs.candidates,
+        &config.outputs.lineage,
+    ]
+    .iter()
+    .copied()
+    .flatten()
+    {
+        with_formats(&mut outputs, run_paths.output_file(name));
+    }
+    if let Some(name) = &config.outputs.quality_report {
+        outputs.push(run_paths.output_file(format!("{}.json", name)));
+    }
+    if let (Some(name), true) = (&config.outputs.issues, report_issues) {
+        with_formats(&mut outputs, run_paths.output_file(name));
+    }
+    outputs
+}
+
+fn scan_inputs(run_folder: &Path, folder: &Path, exclude: &HashSet<&Path>, inputs: &mut Vec<InputFile>) -> Result<(), Error> {
+    let dir_reader = fs::read_dir(folder).into_error(format!("Could not read folder {}", folder.display()))?;
+    for entry in dir_reader {
+        let entry = entry.into_error(format!("Could not read folder {}", folder.display()))?;
+        let path = entry.path();
+        let metadata = entry
+            .metadata()
+            .into_error(format!("Could not read metadata of {}", path.display()))?;
+        if metadata.is_dir() {
+            scan_inputs(run_folder, &path, exclude, inputs)?;
+        } else if !exclude.contains(path.as_path()) {
+            let modified = metadata
+                .modified()
+                .into_error(format!("Could not read modification time of {}", path.display()))?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            inputs.push(InputFile {
+                path: path
+                    .strip_prefix(run_folder)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .into_owned(),
+                size: metadata.len(),
+                modified_secs: modified.as_secs(),
+                modified_nanos: modified.subsec_nanos(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Hash of the configuration and of the command line options that change the outputs
+fn settings_hash(config: &ExperimentConfig, options: &str) -> Result<String, Error> {
+    let config_str = serde_yaml::to_string(config).into_error("could not serialize configuration")?;
+    // FNV-1a, stable across builds unlike std's DefaultHasher
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in config_str.bytes().chain(options.bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Ok(format!("{:016x}", hash))
+}
@@ -0,0 +1,172 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use crate::config::ExperimentConfig;
+use crate::error::{ConvertResult, Error};
+use crate::issues::{Issues, Strictness};
+use crate::paths::RunPaths;
+
+/// Measure name -> value, as read from a `behavior_desc_<id>.txt` or `phenotype_desc_<id>.txt` file
+pub type Descriptor = HashMap<String, Option<f64>>;
+
+/// Reads a `measure value` descriptor file.
+/// Returns `None` if the file is missing or contains only `None` (e.g. the robot was not evaluated).
+/// Missing files, malformed lines and values that are not numbers nor `None` go through `issues`.
+pub fn load_descriptor<P: AsRef<Path>>(path: P, issues: &mut Issues) -> Result<Option<Descriptor>, Error> {
+    let path = path.as_ref();
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            issues.tolerate(Error::io(path, e))?;
+            return Ok(None);
+        }
+    };
+    let mut file_reader = io::BufReader::new(file).lines().enumerate().peekable();
+    if let Some((_, Ok(first_line))) = file_reader.peek() {
+        if first_line == "None" {
+            return Ok(None);
+        }
+    }
+    let mut measures = Descriptor::new();
+    for (line_number, line) in file_reader {
+        let line = line.map_err(|e| Error::io(path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut split = line.trim().split(' ');
+        match (split.next(), split.next(), split.next()) {
+            (Some(measure), Some(value), None) => {
+                let value = match value.parse::<f64>() {
+                    Ok(value) => Some(value),
+                    Err(_) if value == "None" => None,
+                    Err(_) => {
+                        let message = format!("invalid value {:?}", value);
+                        issues.tolerate(Error::malformed_line(path, line_number + 1, Some(measure), message))?;
+                        None
+                    }
+                };
+                measures.insert(measure.to_string(), value);
+            }
+            _ => {
+                let message = format!("expected `measure value`, found {:?}", line);
+                issues.tolerate(Error::malformed_line(path, line_number + 1, None, message))?;
+            }
+        }
+    }
+    Ok(Some(measures))
+}
+
+/// Measures of the descriptors of a run that are not in the configured `measures`, or in them but absent,
+/// collected so that each one is reported once per run instead of once per robot
+#[derive(Debug, Default)]
+pub struct MeasureMismatches {
+    /// Measure -> first descriptor file and number of descriptor files
+    unknown: BTreeMap<String, (PathBuf, usize)>,
+    /// Measure -> first descriptor file and number of descriptor files
+    missing: BTreeMap<String, (PathBuf, usize)>,
+}
+
+impl MeasureMismatches {
+    pub fn check(&mut self, path: &Path, descriptor: &Descriptor, measures: &[String]) {
+        let count = |mismatches: &mut BTreeMap<String, (PathBuf, usize)>, measure: &str| {
+            mismatches.entry(measure.to_string()).or_insert_with(|| (path.to_path_buf(), 0)).1 += 1;
+        };
+        for measure in descriptor.keys().filter(|measure| !measures.contains(measure)) {
+            count(&mut self.unknown, measure);
+        }
+        for measure in missing_measures(descriptor, measures) {
+            count(&mut self.missing, measure);
+        }
+    }
+
+    /// Reports each unknown and missing measure through `issues`
+    pub fn report(self, issues: &mut Issues) -> Result<(), Error> {
+        for (measure, (path, descriptors)) in self.unknown {
+            issues.tolerate(Error::UnknownMeasure { path, measure, descriptors })?;
+        }
+        for (measure, (path, descriptors)) in self.missing {
+            issues.tolerate(Error::MissingMeasure { path, measure, descriptors })?;
+        }
+        Ok(())
+    }
+}
+
+/// Measures of `measures` that are not present in the descriptor
+pub fn missing_measures<'a>(descriptor: &'a Descriptor, measures: &'a [String]) -> impl Iterator<Item = &'a str> {
+    measures
+        .iter()
+        .filter(move |measure| !descriptor.contains_key(measure.as_str()))
+        .map(String::as_str)
+}
+
+/// Which descriptor files are scanned to find the measures to output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiscoveryScope {
+    /// Each run gets the measures found in its own descriptor files
+    Run,
+    /// All the runs of an experiment get the measures found in any of its runs
+    All,
+}
+
+/// Union of the measure names found in the descriptor files
+#[derive(Debug, Default, Clone)]
+pub struct DiscoveredMeasures {
+    pub behavioural: BTreeSet<String>,
+    pub phenotype: BTreeSet<String>,
+}
+
+impl DiscoveredMeasures {
+    /// Reads every descriptor file of the run and collects the measure names
+    pub fn scan(run_paths: &RunPaths) -> Result<Self, Error> {
+        lazy_static! {
+            static ref BEHAVIOUR_FILE_REGEX: Regex = Regex::new(r"^behavior_desc_(\d+).txt$").unwrap();
+            static ref PHENOTYPE_FILE_REGEX: Regex = Regex::new(r"^phenotype_desc_(\d+).txt$").unwrap();
+        }
+        Ok(DiscoveredMeasures {
+            behavioural: scan_folder(&run_paths.behavioural_descriptors_folder(), &BEHAVIOUR_FILE_REGEX)?,
+            phenotype: scan_folder(&run_paths.phenotype_descriptors_folder(), &PHENOTYPE_FILE_REGEX)?,
+        })
+    }
+
+    pub fn merge(&mut self, other: DiscoveredMeasures) {
+        self.behavioural.extend(other.behavioural);
+        self.phenotype.extend(other.phenotype);
+    }
+
+    /// Copy of the configuration where the discovered measures that are not already listed
+    /// are appended, in alphabetical order, to the configured ones
+    pub fn apply_to(&self, config: &ExperimentConfig) -> ExperimentConfig {
+        let extend = |listed: &[String], discovered: &BTreeSet<String>| {
+            let mut measures = listed.to_vec();
+            measures.extend(discovered.iter().filter(|m| !listed.contains(m)).cloned());
+            measures
+        };
+        ExperimentConfig {
+            behavioural_measures: extend(&config.behavioural_measures, &self.behavioural),
+            phenotype_measures: extend(&config.phenotype_measures, &self.phenotype),
+            ..config.clone()
+        }
+    }
+}
+
+fn scan_folder(folder: &Path, filename_regex: &Regex) -> Result<BTreeSet<String>, Error> {
+    let mut measures = BTreeSet::new();
+    let dir_reader = fs::read_dir(folder)
+        .into_error(format!("Could not open descriptors folder ({})", folder.display()))?;
+    for entry in dir_reader {
+        let entry = entry.into_error(format!("Could not read descriptors folder ({})", folder.display()))?;
+        let filename = entry.file_name();
+        if !filename_regex.is_match(filename.to_str().unwrap_or("")) {
+            continue;
+        }
+        // problems in the descriptors are dealt with when the run is consolidated
+        let mut issues = Issues::new(Strictness::Report);
+        if let Some(descriptor) = load_descriptor(entry.path(), &mut issues)? {
+            measures.extend(descriptor.into_keys());
+        }
+    }
+    Ok(measures)
+}
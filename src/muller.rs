@@ -0,0 +1,233 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::table::{ColumnDef, ColumnType, Table, Value};
+
+/// First and last generation of a species, and the species it split from
+#[derive(Debug)]
+struct Lifespan {
+    birth: u64,
+    last_seen: u64,
+    parent: Option<u64>,
+}
+
+/// Abundance of the species over the generations of a run, laid out for Muller and stacked area plots
+#[derive(Debug, Default)]
+pub struct SpeciesDynamics {
+    /// generation -> species -> individuals
+    populations: BTreeMap<u64, BTreeMap<u64, usize>>,
+    lifespans: BTreeMap<u64, Lifespan>,
+}
+
+impl SpeciesDynamics {
+    /// Collects the `(robot_id, generation, species)` memberships of the snapshots.
+    /// The parent of a species is the species most of the parents of its founders (its members in the
+    /// generation it appears) belonged to, ties going to the lowest id. Species of the initial population
+    /// have no parent.
+    pub fn new<I>(members: I, phylogeny: &HashMap<u64, Vec<u64>>) -> Self
+    where
+        I: IntoIterator<Item = (u64, u64, u64)>,
+    {
+        let mut dynamics = SpeciesDynamics::default();
+        let mut robot_species: HashMap<u64, Vec<(u64, u64)>> = HashMap::new();
+        for (robot_id, generation, species) in members {
+            *dynamics
+                .populations
+                .entry(generation)
+                .or_default()
+                .entry(species)
+                .or_default() += 1;
+            let lifespan = dynamics.lifespans.entry(species).or_insert(Lifespan {
+                birth: generation,
+                last_seen: generation,
+                parent: None,
+            });
+            lifespan.birth = lifespan.birth.min(generation);
+            lifespan.last_seen = lifespan.last_seen.max(generation);
+            robot_species.entry(robot_id).or_default().push((generation, species));
+        }
+        for history in robot_species.values_mut() {
+            history.sort_unstable();
+        }
+
+        // species of a robot in the last generation before `generation`, or when it first appeared
+        let species_before = |robot_id: &u64, generation: u64| {
+            robot_species.get(robot_id).and_then(|history| {
+                history
+                    .iter()
+                    .rev()
+                    .find(|(robot_generation, _)| *robot_generation < generation)
+                    .or_else(|| history.first())
+                    .map(|(_, species)| *species)
+            })
+        };
+
+        let mut votes: BTreeMap<u64, BTreeMap<u64, usize>> = BTreeMap::new();
+        for (robot_id, history) in &robot_species {
+            for (generation, species) in history {
+                if *generation != dynamics.lifespans[species].birth {
+                    continue;
+                }
+                for parent_id in phylogeny.get(robot_id).into_iter().flatten() {
+                    match species_before(parent_id, *generation) {
+                        Some(parent_species) if parent_species != *species => {
+                            *votes.entry(*species).or_default().entry(parent_species).or_default() += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for (species, species_votes) in votes {
+            let best = species_votes
+                .into_iter()
+                .max_by(|(species_a, count_a), (species_b, count_b)| count_a.cmp(count_b).then(species_b.cmp(species_a)));
+            if let Some(lifespan) = dynamics.lifespans.get_mut(&species) {
+                lifespan.parent = best.map(|(parent_species, _)| parent_species);
+            }
+        }
+        dynamics
+    }
+
+    /// Individuals of every species in every generation, zero when the species is not alive
+    pub fn population_table(&self) -> Table {
+        let mut table = Table::new(vec![
+            ColumnDef::new("generation", ColumnType::UInt64),
+            ColumnDef::new("species", ColumnType::UInt64),
+            ColumnDef::new("count", ColumnType::UInt64),
+            ColumnDef::new("proportion", ColumnType::Float64),
+        ]);
+        for (generation, populations) in &self.populations {
+            let total: usize = populations.values().sum();
+            for species in self.lifespans.keys() {
+                let count = populations.get(species).copied().unwrap_or(0);
+                table.push(vec![
+                    Value::UInt(*generation),
+                    Value::UInt(*species),
+                    Value::UInt(count as u64),
+                    Value::Float(count as f64 / total as f64),
+                ]);
+            }
+        }
+        table
+    }
+
+    /// Parent-child pairs of species, the species without a parent are not listed
+    pub fn edges_table(&self) -> Table {
+        let mut table = Table::new(vec![
+            ColumnDef::new("parent_species", ColumnType::UInt64),
+            ColumnDef::new("species", ColumnType::UInt64),
+        ]);
+        for (species, lifespan) in &self.lifespans {
+            if let Some(parent) = lifespan.parent {
+                table.push(vec![Value::UInt(parent), Value::UInt(*species)]);
+            }
+        }
+        table
+    }
+
+    /// Birth and extinction generation of every species, the extinction is the first generation the
+    /// species is not found in anymore and null for the species alive in the last generation
+    pub fn lifespans_table(&self) -> Table {
+        let mut table = Table::new(vec![
+            ColumnDef::new("species", ColumnType::UInt64),
+            ColumnDef::new("parent_species", ColumnType::UInt64),
+            ColumnDef::new("birth_generation", ColumnType::UInt64),
+            ColumnDef::new("extinction_generation", ColumnType::UInt64),
+        ]);
+        for (species, lifespan) in &self.lifespans {
+            let extinction = self
+                .populations
+                .range(lifespan.last_seen + 1..)
+                .next()
+                .map(|(generation, _)| *generation);
+            table.push(vec![
+                Value::UInt(*species),
+                lifespan.parent.into(),
+                Value::UInt(lifespan.birth),
+                extinction.into(),
+            ]);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dynamics(members: &[(u64, u64, u64)], parents: &[(u64, &[u64])]) -> SpeciesDynamics {
+        let phylogeny = parents.iter().map(|(id, parents)| (*id, parents.to_vec())).collect();
+        SpeciesDynamics::new(members.iter().copied(), &phylogeny)
+    }
+
+    /// `(species, parent_species, birth_generation, extinction_generation)` rows of the lifespans table
+    fn lifespans(dynamics: &SpeciesDynamics) -> Vec<Vec<Value>> {
+        dynamics.lifespans_table().rows
+    }
+
+    fn lifespan(species: u64, parent: Option<u64>, birth: u64, extinction: Option<u64>) -> Vec<Value> {
+        vec![Value::UInt(species), parent.into(), Value::UInt(birth), extinction.into()]
+    }
+
+    #[test]
+    fn species_split_from_the_species_of_most_founder_parents() {
+        // species 3 appears in generation 1, two of its founders' parents are in species 1 and one in species 2
+        let members = [
+            (1, 0, 1),
+            (2, 0, 1),
+            (3, 0, 2),
+            (1, 1, 1),
+            (3, 1, 2),
+            (4, 1, 3),
+            (5, 1, 3),
+        ];
+        let dynamics = dynamics(&members, &[(4, &[1, 3]), (5, &[2])]);
+        assert_eq!(
+            lifespans(&dynamics),
+            vec![lifespan(1, None, 0, None), lifespan(2, None, 0, None), lifespan(3, Some(1), 1, None)]
+        );
+        assert_eq!(dynamics.edges_table().rows, vec![vec![Value::UInt(1), Value::UInt(3)]]);
+    }
+
+    #[test]
+    fn tied_parent_species_go_to_the_lowest_id() {
+        let members = [(1, 0, 7), (2, 0, 4), (1, 1, 7), (3, 1, 9)];
+        let dynamics = dynamics(&members, &[(3, &[1, 2])]);
+        assert_eq!(lifespans(&dynamics)[2], lifespan(9, Some(4), 1, None));
+    }
+
+    #[test]
+    fn parents_of_the_own_species_do_not_vote() {
+        // robot 4 founds species 2 with a parent already in species 2 and one in species 1
+        let members = [(1, 0, 1), (2, 0, 2), (3, 1, 2), (4, 1, 2)];
+        let dynamics = dynamics(&members, &[(3, &[2]), (4, &[2, 1])]);
+        assert_eq!(lifespans(&dynamics)[1], lifespan(2, None, 0, None));
+    }
+
+    #[test]
+    fn parent_only_seen_later_gives_its_first_species() {
+        // robot 9 is missing from the snapshot of generation 0, its species is the one it is first seen in
+        let members = [(1, 0, 1), (9, 1, 1), (5, 1, 2), (9, 2, 2), (5, 2, 2)];
+        let dynamics = dynamics(&members, &[(5, &[9, 42])]);
+        assert_eq!(lifespans(&dynamics)[1], lifespan(2, Some(1), 1, None));
+    }
+
+    #[test]
+    fn extinct_species_end_at_the_first_generation_without_them() {
+        // species 2 is alive in generations 0 and 1, and never seen again
+        let members = [(1, 0, 1), (2, 0, 2), (1, 1, 1), (2, 1, 2), (1, 2, 1), (3, 3, 1)];
+        let dynamics = dynamics(&members, &[(3, &[1])]);
+        assert_eq!(
+            lifespans(&dynamics),
+            vec![lifespan(1, None, 0, None), lifespan(2, None, 0, Some(2))]
+        );
+
+        let population = dynamics.population_table();
+        let species_2: Vec<&Value> = population
+            .rows
+            .iter()
+            .filter(|row| row[1] == Value::UInt(2))
+            .map(|row| &row[2])
+            .collect();
+        assert_eq!(species_2, vec![&Value::UInt(1), &Value::UInt(1), &Value::UInt(0), &Value::UInt(0)]);
+    }
+}
@@ -0,0 +1,154 @@
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use clap::ValueEnum;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use crate::error::{ConvertResult, Error};
+use crate::table::{ColumnDef, Table};
+
+/// File formats the consolidated tables can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Tab separated values, with the null token for missing values
+    Tsv,
+    /// Apache Parquet, with typed nullable columns
+    Parquet,
+    /// Arrow IPC file (Feather v2), can be memory-mapped by pandas and R
+    Feather,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Feather => "feather",
+        }
+    }
+}
+
+/// Writes the table once for each format, as `<stem>.<format extension>`
+pub fn write_table<P: AsRef<Path>>(
+    table: &Table,
+    stem: P,
+    formats: &[OutputFormat],
+    null_token: &str,
+) -> Result<(), Error> {
+    for format in formats {
+        let path = with_extension(stem.as_ref(), format.extension());
+        match format {
+            OutputFormat::Tsv => table.write_tsv(&path, null_token)?,
+            OutputFormat::Parquet => write_parquet(table, &path)?,
+            OutputFormat::Feather => write_feather(table, &path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Removes the files `write_table` writes, e.g. for a table left out because the run has no data for it
+pub fn remove_table<P: AsRef<Path>>(stem: P, formats: &[OutputFormat]) -> Result<(), Error> {
+    for format in formats {
+        let path = with_extension(stem.as_ref(), format.extension());
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e).into_error(format!("could not remove {}", path.display()));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reads back a table written by `write_table`, from the first of the formats whose file exists.
+/// Typed formats are preferred, TSV columns not in `known_columns` are read as Float64, or Utf8 if not all numbers.
+pub fn read_table<P: AsRef<Path>>(
+    stem: P,
+    formats: &[OutputFormat],
+    null_token: &str,
+    known_columns: &[ColumnDef],
+) -> Result<Table, Error> {
+    let mut formats = formats.to_vec();
+    formats.sort_by_key(|format| *format == OutputFormat::Tsv);
+    for format in formats {
+        let path = with_extension(stem.as_ref(), format.extension());
+        if !path.is_file() {
+            continue;
+        }
+        return match format {
+            OutputFormat::Tsv => Table::read_tsv(&path, null_token, known_columns),
+            OutputFormat::Parquet => read_parquet(&path),
+            OutputFormat::Feather => read_feather(&path),
+        };
+    }
+    Err(Error::new(format!("no output file found for {}", stem.as_ref().display())))
+}
+
+fn read_parquet(path: &Path) -> Result<Table, Error> {
+    let file = fs::File::open(path).into_error(format!("could not open {}", path.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .into_error(format!("could not read parquet file {}", path.display()))?;
+    let schema = builder.schema().clone();
+    let batches = builder
+        .build()
+        .into_error(format!("could not read parquet file {}", path.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .into_error(format!("could not read parquet file {}", path.display()))?;
+    Table::from_record_batches(&schema, &batches)
+}
+
+fn read_feather(path: &Path) -> Result<Table, Error> {
+    let file = fs::File::open(path).into_error(format!("could not open {}", path.display()))?;
+    let reader = FileReader::try_new(file, None)
+        .into_error(format!("could not read arrow file {}", path.display()))?;
+    let schema = reader.schema();
+    let batches = reader
+        .collect::<Result<Vec<_>, _>>()
+        .into_error(format!("could not read arrow file {}", path.display()))?;
+    Table::from_record_batches(&schema, &batches)
+}
+
+fn write_parquet(table: &Table, path: &Path) -> Result<(), Error> {
+    let batch = table.to_record_batch()?;
+    let file = fs::File::create(path).into_error(format!("could not create {}", path.display()))?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))
+        .into_error(format!("could not start parquet file {}", path.display()))?;
+    writer
+        .write(&batch)
+        .into_error(format!("could not write parquet file {}", path.display()))?;
+    writer
+        .close()
+        .into_error(format!("could not finish parquet file {}", path.display()))?;
+    Ok(())
+}
+
+fn write_feather(table: &Table, path: &Path) -> Result<(), Error> {
+    let batch = table.to_record_batch()?;
+    let file = fs::File::create(path).into_error(format!("could not create {}", path.display()))?;
+    let mut writer = FileWriter::try_new(file, &batch.schema())
+        .into_error(format!("could not start arrow file {}", path.display()))?;
+    writer
+        .write(&batch)
+        .into_error(format!("could not write arrow file {}", path.display()))?;
+    writer
+        .finish()
+        .into_error(format!("could not finish arrow file {}", path.display()))?;
+    Ok(())
+}
+
+/// Appends the extension to the path, keeping any dot already in the file name
+pub fn with_extension(stem: &Path, extension: &str) -> PathBuf {
+    let mut path = OsString::from(stem.as_os_str());
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
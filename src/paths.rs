@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use crate::config::InputLayout;
+
+/// Input and output locations of a single run
+#[derive(Debug, Clone)]
+pub struct RunPaths {
+    pub input: PathBuf,
+    output: Option<PathBuf>,
+    layout: InputLayout,
+}
+
+impl RunPaths {
+    /// Paths for a run that is read from `data_root/experiment/run`.
+    /// If `output_root` is set, results go in `output_root/experiment/run` and the run folder is only read,
+    /// otherwise they are written inside the run folder.
+    pub fn new<P: AsRef<Path>>(
+        data_root: P,
+        output_root: Option<P>,
+        experiment: &str,
+        run: &str,
+        layout: &InputLayout,
+    ) -> Self {
+        RunPaths {
+            input: data_root.as_ref().join(experiment).join(run),
+            output: output_root.map(|root| root.as_ref().join(experiment).join(run)),
+            layout: layout.clone(),
+        }
+    }
+
+    /// Makes sure the output folder exists
+    pub fn create_output_dir(&self) -> io::Result<()> {
+        match &self.output {
+            Some(output) => fs::create_dir_all(output),
+            None => Ok(()),
+        }
+    }
+
+    pub fn data_fullevolution(&self) -> PathBuf {
+        self.input.join(&self.layout.data_fullevolution)
+    }
+
+    pub fn generations(&self) -> PathBuf {
+        self.input.join(&self.layout.generations)
+    }
+
+    pub fn identifiers<P: AsRef<Path>>(&self, generation_path: P) -> PathBuf {
+        generation_path.as_ref().join(&self.layout.identifiers)
+    }
+
+    pub fn extra<P: AsRef<Path>>(&self, generation_path: P) -> PathBuf {
+        generation_path.as_ref().join(&self.layout.extra)
+    }
+
+    pub fn fitness(&self) -> PathBuf {
+        self.data_fullevolution().join(&self.layout.fitness)
+    }
+
+    pub fn phylogeny_folder(&self) -> PathBuf {
+        self.data_fullevolution().join(&self.layout.phylogeny)
+    }
+
+    pub fn phenotype_descriptors_folder(&self) -> PathBuf {
+        self.data_fullevolution().join(&self.layout.phenotype_descriptors)
+    }
+
+    pub fn behavioural_descriptors_folder(&self) -> PathBuf {
+        self.data_fullevolution().join(&self.layout.behavioural_descriptors)
+    }
+
+    pub fn phenotype_descriptor(&self, robot_id: u64) -> PathBuf {
+        self.phenotype_descriptors_folder()
+            .join(format!("phenotype_desc_{}.txt", robot_id))
+    }
+
+    pub fn behaviour_descriptor(&self, robot_id: u64) -> PathBuf {
+        self.behavioural_descriptors_folder()
+            .join(format!("behavior_desc_{}.txt", robot_id))
+    }
+
+    /// Path of a generated file
+    pub fn output_file<S: AsRef<str>>(&self, filename: S) -> PathBuf {
+        self.output_dir().join(filename.as_ref())
+    }
+
+    /// Path of the generated phylogeny file, which lives in `data_fullevolution` when writing inside the run folder
+    pub fn phylogeny_output_file<S: AsRef<str>>(&self, filename: S) -> PathBuf {
+        match &self.output {
+            Some(output) => output.join(filename.as_ref()),
+            None => self.data_fullevolution().join(filename.as_ref()),
+        }
+    }
+
+    /// Path of a generated file relative to the output folder of the run, so that it does not depend on the
+    /// working directory. Paths outside of the output folder are kept as they are.
+    pub fn relative_output_file<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        path.strip_prefix(self.output_dir()).unwrap_or(path).to_path_buf()
+    }
+
+    /// Path of a generated file from its path relative to the output folder of the run
+    pub fn resolve_output_file<P: AsRef<Path>>(&self, relative_path: P) -> PathBuf {
+        self.output_dir().join(relative_path)
+    }
+
+    fn output_dir(&self) -> &Path {
+        self.output.as_deref().unwrap_or(&self.input)
+    }
+}
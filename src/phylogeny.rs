@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use crate::error::{ConvertResult, Error};
+
+/// Attributes of a robot in the exported trees
+#[derive(Debug, Clone, Default)]
+pub struct RobotNode {
+    pub fitness: Option<f64>,
+    /// First generation the robot is found in
+    pub generation: Option<u64>,
+    /// Species of the robot in its first generation
+    pub species: Option<u64>,
+}
+
+/// Genealogy of a run, exported for network tools and tree viewers
+#[derive(Debug, Default)]
+pub struct PhylogenyGraph {
+    nodes: BTreeMap<u64, RobotNode>,
+    /// child -> parents
+    parents: BTreeMap<u64, Vec<u64>>,
+}
+
+impl PhylogenyGraph {
+    /// Parents that are not in `nodes` are added without attributes
+    pub fn new(mut nodes: BTreeMap<u64, RobotNode>, phylogeny: &HashMap<u64, Vec<u64>>) -> Self {
+        for (child, parents) in phylogeny {
+            for robot_id in parents.iter().chain(Some(child)) {
+                nodes.entry(*robot_id).or_default();
+            }
+        }
+        PhylogenyGraph {
+            nodes,
+            parents: phylogeny
+                .iter()
+                .filter(|(_, parents)| !parents.is_empty())
+                .map(|(child, parents)| (*child, parents.clone()))
+                .collect(),
+        }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.parents
+            .iter()
+            .flat_map(|(child, parents)| parents.iter().map(move |parent| (*parent, *child)))
+    }
+
+    /// GraphML with fitness, generation and species as node attributes, edges go from parent to child
+    pub fn write_graphml<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write_file(path.as_ref(), |writer| {
+            writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(writer, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+            writeln!(writer, r#"  <key id="fitness" for="node" attr.name="fitness" attr.type="double"/>"#)?;
+            writeln!(writer, r#"  <key id="generation" for="node" attr.name="generation" attr.type="long"/>"#)?;
+            writeln!(writer, r#"  <key id="species" for="node" attr.name="species" attr.type="long"/>"#)?;
+            writeln!(writer, r#"  <graph id="phylogeny" edgedefault="directed">"#)?;
+            for (robot_id, node) in &self.nodes {
+                writeln!(writer, r#"    <node id="{}">"#, robot_id)?;
+                if let Some(fitness) = node.fitness {
+                    writeln!(writer, r#"      <data key="fitness">{}</data>"#, fitness)?;
+                }
+                if let Some(generation) = node.generation {
+                    writeln!(writer, r#"      <data key="generation">{}</data>"#, generation)?;
+                }
+                if let Some(species) = node.species {
+                    writeln!(writer, r#"      <data key="species">{}</data>"#, species)?;
+                }
+                writeln!(writer, "    </node>")?;
+            }
+            for (parent, child) in self.edges() {
+                writeln!(writer, r#"    <edge source="{}" target="{}"/>"#, parent, child)?;
+            }
+            writeln!(writer, "  </graph>")?;
+            writeln!(writer, "</graphml>")
+        })
+    }
+
+    /// Graphviz digraph with the node attributes, edges go from parent to child
+    pub fn write_dot<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write_file(path.as_ref(), |writer| {
+            writeln!(writer, "digraph phylogeny {{")?;
+            for (robot_id, node) in &self.nodes {
+                let mut attributes = Vec::new();
+                if let Some(fitness) = node.fitness {
+                    attributes.push(format!("fitness={}", fitness));
+                }
+                if let Some(generation) = node.generation {
+                    attributes.push(format!("generation={}", generation));
+                }
+                if let Some(species) = node.species {
+                    attributes.push(format!("species={}", species));
+                }
+                if attributes.is_empty() {
+                    writeln!(writer, "  {};", robot_id)?;
+                } else {
+                    writeln!(writer, "  {} [{}];", robot_id, attributes.join(", "))?;
+                }
+            }
+            for (parent, child) in self.edges() {
+                writeln!(writer, "  {} -> {};", parent, child)?;
+            }
+            writeln!(writer, "}}")
+        })
+    }
+
+    /// One Newick tree per robot without parents, on its own line. Branch lengths are the generations
+    /// between parent and child, when both are known. Fails if a robot has more than one parent.
+    pub fn write_newick<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut children: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for (child, parents) in &self.parents {
+            if parents.len() > 1 {
+                return Err(Error::new(format!(
+                    "Newick needs single parent lineages, robot {} has {} parents",
+                    child,
+                    parents.len()
+                )));
+            }
+            children.entry(parents[0]).or_default().push(*child);
+        }
+        let roots = self.nodes.keys().filter(|robot_id| !self.parents.contains_key(robot_id));
+        let trees = roots
+            .map(|root| self.newick_tree(*root, &children))
+            .collect::<Vec<_>>();
+        write_file(path.as_ref(), |writer| {
+            for tree in &trees {
+                writeln!(writer, "{};", tree)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Newick subtree of `root`, built without recursion as lineages can be thousands of generations long
+    fn newick_tree(&self, root: u64, children: &BTreeMap<u64, Vec<u64>>) -> String {
+        enum Step {
+            Enter(u64),
+            Comma,
+            /// Closes the list of children of an inner node
+            Exit(u64),
+        }
+        let mut tree = String::new();
+        let mut visited = BTreeSet::new();
+        visited.insert(root);
+        let mut stack = vec![Step::Enter(root)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(robot_id) => {
+                    let robot_children: Vec<u64> = children
+                        .get(&robot_id)
+                        .into_iter()
+                        .flatten()
+                        .copied()
+                        .filter(|child| visited.insert(*child))
+                        .collect();
+                    if robot_children.is_empty() {
+                        self.push_newick_label(&mut tree, robot_id);
+                    } else {
+                        tree.push('(');
+                        stack.push(Step::Exit(robot_id));
+                        for (i, child) in robot_children.into_iter().enumerate().rev() {
+                            stack.push(Step::Enter(child));
+                            if i > 0 {
+                                stack.push(Step::Comma);
+                            }
+                        }
+                    }
+                }
+                Step::Comma => tree.push(','),
+                Step::Exit(robot_id) => {
+                    tree.push(')');
+                    self.push_newick_label(&mut tree, robot_id);
+                }
+            }
+        }
+        tree
+    }
+
+    /// Id of the robot, and generations since its parent as branch length
+    fn push_newick_label(&self, tree: &mut String, robot_id: u64) {
+        tree.push_str(&robot_id.to_string());
+        let parent = self.parents.get(&robot_id).and_then(|parents| parents.first());
+        let generations = parent.and_then(|parent| {
+            let child_generation = self.nodes.get(&robot_id)?.generation?;
+            let parent_generation = self.nodes.get(parent)?.generation?;
+            child_generation.checked_sub(parent_generation)
+        });
+        if let Some(generations) = generations {
+            tree.push_str(&format!(":{}", generations));
+        }
+    }
+}
+
+fn write_file<F>(path: &Path, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> io::Result<()>,
+{
+    let file = fs::File::create(path).into_error(format!("could not create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)
+        .and_then(|_| writer.flush())
+        .into_error(format!("could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(generation: Option<u64>) -> RobotNode {
+        RobotNode {
+            generation,
+            ..RobotNode::default()
+        }
+    }
+
+    /// Robots 1 and 6 start the run, 5 has no known generation and 7 is only known as the parent of 8
+    fn graph() -> PhylogenyGraph {
+        let nodes = [(1, Some(0)), (2, Some(1)), (3, Some(3)), (4, Some(2)), (5, None), (6, Some(0)), (8, Some(4))]
+            .iter()
+            .map(|(robot_id, generation)| (*robot_id, node(*generation)))
+            .collect();
+        let phylogeny = [(2, vec![1]), (3, vec![1]), (4, vec![2]), (5, vec![2]), (6, vec![]), (8, vec![7])]
+            .iter()
+            .cloned()
+            .collect();
+        PhylogenyGraph::new(nodes, &phylogeny)
+    }
+
+    fn newick(graph: &PhylogenyGraph, name: &str) -> Result<String, Error> {
+        let path = std::env::temp_dir().join(format!("phylogeny_{}_{}.nwk", name, std::process::id()));
+        let result = graph.write_newick(&path).map(|_| fs::read_to_string(&path).unwrap());
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn newick_trees_of_every_root() {
+        assert_eq!(newick(&graph(), "roots").unwrap(), "((4:1,5)2:1,3:3)1;\n6;\n(8)7;\n");
+    }
+
+    #[test]
+    fn newick_long_lineage() {
+        let length = 100_000;
+        let nodes = (0..length).map(|robot_id| (robot_id, node(Some(robot_id)))).collect();
+        let phylogeny = (1..length).map(|robot_id| (robot_id, vec![robot_id - 1])).collect();
+        let tree = newick(&PhylogenyGraph::new(nodes, &phylogeny), "lineage").unwrap();
+        assert!(tree.starts_with(&"(".repeat(length as usize - 1)));
+        assert!(tree.ends_with(")2:1)1:1)0;\n"));
+    }
+
+    #[test]
+    fn newick_rejects_two_parents() {
+        let phylogeny = [(3, vec![1, 2])].iter().cloned().collect();
+        let graph = PhylogenyGraph::new(BTreeMap::new(), &phylogeny);
+        match newick(&graph, "two_parents") {
+            Err(e) => assert!(e.to_string().contains("robot 3 has 2 parents"), "unexpected error {}", e),
+            Ok(tree) => panic!("unexpected tree {:?}", tree),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use crate::error::{ConvertResult, Error};
+use crate::measures::Descriptor;
+use crate::table::{Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorKind {
+    Behavioural,
+    Phenotype,
+}
+
+/// A phylogeny file naming a parent that is not a robot of the run
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct UnknownParent {
+    pub robot_id: u64,
+    pub parent_id: u64,
+}
+
+/// What was missing or incomplete in the inputs of a run, written as JSON next to its outputs
+#[derive(Debug, Default, Serialize)]
+pub struct QualityReport {
+    /// Robots in the fitness file
+    pub robots: usize,
+    pub generations: usize,
+    /// Rows of all_measures
+    pub rows: usize,
+    /// Robots in the fitness file without a behavioural descriptor file
+    pub robots_without_behavioural_descriptor: Vec<u64>,
+    /// Robots in the fitness file without a phenotype descriptor file
+    pub robots_without_phenotype_descriptor: Vec<u64>,
+    /// Robots whose behavioural descriptor file contains `None`
+    pub none_behavioural_descriptors: Vec<u64>,
+    /// Robots whose phenotype descriptor file contains `None`
+    pub none_phenotype_descriptors: Vec<u64>,
+    /// Robots in the identifiers of a generation but not in the fitness file
+    pub robots_without_fitness: Vec<u64>,
+    /// Robots whose fitness is `None` or not a number, written as null
+    pub none_fitness: Vec<u64>,
+    pub unknown_parents: Vec<UnknownParent>,
+    pub generations_without_extra: Vec<u64>,
+    /// Null values in each column of all_measures
+    pub na_counts: BTreeMap<String, usize>,
+}
+
+impl QualityReport {
+    /// Records whether the descriptor of the robot is missing or contains `None`
+    pub fn check_descriptor(&mut self, kind: DescriptorKind, robot_id: u64, path: &Path, descriptor: &Option<Descriptor>) {
+        if descriptor.is_some() {
+            return;
+        }
+        let robots = match (kind, path.is_file()) {
+            (DescriptorKind::Behavioural, false) => &mut self.robots_without_behavioural_descriptor,
+            (DescriptorKind::Phenotype, false) => &mut self.robots_without_phenotype_descriptor,
+            (DescriptorKind::Behavioural, true) => &mut self.none_behavioural_descriptors,
+            (DescriptorKind::Phenotype, true) => &mut self.none_phenotype_descriptors,
+        };
+        robots.push(robot_id);
+    }
+
+    /// Compares the robots of the fitness file with the ones of the snapshots and of the phylogeny
+    pub fn check_robots<'a, I>(&mut self, fitness_robots: &HashSet<u64>, snapshot_robots: I, phylogeny: &HashMap<u64, Vec<u64>>)
+    where
+        I: IntoIterator<Item = &'a u64>,
+    {
+        self.robots = fitness_robots.len();
+        let mut known_robots = fitness_robots.clone();
+        for robot_id in snapshot_robots {
+            known_robots.insert(*robot_id);
+            if !fitness_robots.contains(robot_id) {
+                self.robots_without_fitness.push(*robot_id);
+            }
+        }
+        known_robots.extend(phylogeny.keys());
+        for (robot_id, parents) in phylogeny {
+            self.unknown_parents.extend(
+                parents
+                    .iter()
+                    .filter(|parent_id| !known_robots.contains(parent_id))
+                    .map(|parent_id| UnknownParent {
+                        robot_id: *robot_id,
+                        parent_id: *parent_id,
+                    }),
+            );
+        }
+    }
+
+    pub fn count_nulls(&mut self, table: &Table) {
+        self.rows = table.rows.len();
+        for (i, column) in table.columns.iter().enumerate() {
+            let nulls = table.rows.iter().filter(|row| row[i] == Value::Null).count();
+            self.na_counts.insert(column.name.clone(), nulls);
+        }
+    }
+
+    /// Writes the report as pretty printed JSON, with the robot lists sorted
+    pub fn write<P: AsRef<Path>>(mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        for robots in [
+            &mut self.robots_without_behavioural_descriptor,
+            &mut self.robots_without_phenotype_descriptor,
+            &mut self.none_behavioural_descriptors,
+            &mut self.none_phenotype_descriptors,
+            &mut self.robots_without_fitness,
+            &mut self.none_fitness,
+            &mut self.generations_without_extra,
+        ] {
+            robots.sort_unstable();
+        }
+        self.unknown_parents.sort_unstable();
+        let report = serde_json::to_string_pretty(&self).into_error("could not serialize quality report")?;
+        fs::write(path, report + "\n").into_error(format!("could not write {}", path.display()))
+    }
+}
@@ -0,0 +1,234 @@
+use clap::ValueEnum;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::data::vector::Vector2;
+use crate::error::{ConvertResult, Error};
+
+/// How many runs share a SQLite database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SqliteScope {
+    /// One database per experiment, in `<experiment>/consolidated.sqlite`
+    Experiment,
+    /// One database for the whole data root, in `consolidated.sqlite`
+    Root,
+}
+
+impl SqliteScope {
+    /// Location of the database for the experiment, under `root`
+    pub fn database_path<P: AsRef<Path>>(&self, root: P, experiment: &str) -> PathBuf {
+        match self {
+            SqliteScope::Experiment => root.as_ref().join(experiment).join("consolidated.sqlite"),
+            SqliteScope::Root => root.as_ref().join("consolidated.sqlite"),
+        }
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    experiment TEXT NOT NULL,
+    run TEXT NOT NULL,
+    PRIMARY KEY (experiment, run)
+);
+CREATE TABLE IF NOT EXISTS robots (
+    experiment TEXT NOT NULL,
+    run TEXT NOT NULL,
+    robot_id INTEGER NOT NULL,
+    fitness REAL,
+    pos_start_x REAL,
+    pos_start_y REAL,
+    pos_end_x REAL,
+    pos_end_y REAL,
+    PRIMARY KEY (experiment, run, robot_id)
+);
+CREATE TABLE IF NOT EXISTS snapshots (
+    experiment TEXT NOT NULL,
+    run TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    robot_id INTEGER NOT NULL,
+    species_id INTEGER,
+    PRIMARY KEY (experiment, run, generation, robot_id)
+);
+CREATE TABLE IF NOT EXISTS parents (
+    experiment TEXT NOT NULL,
+    run TEXT NOT NULL,
+    child_id INTEGER NOT NULL,
+    parent_id INTEGER NOT NULL,
+    rank INTEGER NOT NULL,
+    PRIMARY KEY (experiment, run, child_id, rank)
+);
+CREATE TABLE IF NOT EXISTS behaviour_measures (
+    experiment TEXT NOT NULL,
+    run TEXT NOT NULL,
+    robot_id INTEGER NOT NULL,
+    measure TEXT NOT NULL,
+    value REAL,
+    PRIMARY KEY (experiment, run, robot_id, measure)
+);
+CREATE TABLE IF NOT EXISTS phenotype_measures (
+    experiment TEXT NOT NULL,
+    run TEXT NOT NULL,
+    robot_id INTEGER NOT NULL,
+    measure TEXT NOT NULL,
+    value REAL,
+    PRIMARY KEY (experiment, run, robot_id, measure)
+);
+CREATE INDEX IF NOT EXISTS snapshots_robot ON snapshots (experiment, run, robot_id);
+CREATE INDEX IF NOT EXISTS parents_parent ON parents (experiment, run, parent_id);
+";
+
+const TABLES: &[&str] = &[
+    "runs",
+    "robots",
+    "snapshots",
+    "parents",
+    "behaviour_measures",
+    "phenotype_measures",
+];
+
+/// A robot of the run, with its fitness and the positions of its first snapshot
+#[derive(Debug, Clone)]
+pub struct RobotRecord {
+    pub robot_id: u64,
+    pub fitness: Option<f64>,
+    pub start_position: Option<Vector2<f64>>,
+    pub end_position: Option<Vector2<f64>>,
+}
+
+/// Everything a run stores in the database, one entry per robot (not per robot and generation)
+#[derive(Debug, Default)]
+pub struct RunRecords {
+    pub robots: Vec<RobotRecord>,
+    /// (generation, robot_id, species_id)
+    pub snapshots: Vec<(u64, u64, Option<u64>)>,
+    /// (child_id, parent_id, rank of the parent starting from 1)
+    pub parents: Vec<(u64, u64, usize)>,
+    /// (robot_id, measure, value)
+    pub behaviour_measures: Vec<(u64, String, Option<f64>)>,
+    /// (robot_id, measure, value)
+    pub phenotype_measures: Vec<(u64, String, Option<f64>)>,
+}
+
+/// SQLite database with normalized tables, shared between the workers consolidating its runs
+pub struct SqliteDatabase {
+    /// Canonical, so that it does not depend on the working directory
+    path: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl SqliteDatabase {
+    /// Opens the database, creating the file and the tables if missing
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)
+                .into_error(format!("could not create folder {}", folder.display()))?;
+        }
+        let connection = Connection::open(path)
+            .into_error(format!("could not open database {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .into_error(format!("could not create tables in {}", path.display()))?;
+        Ok(SqliteDatabase {
+            path: path
+                .canonicalize()
+                .into_error(format!("could not resolve database path {}", path.display()))?,
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// True if the records of the run were inserted, e.g. by a previous consolidation
+    pub fn has_run(&self, experiment: &str, run: &str) -> Result<bool, Error> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM runs WHERE experiment = ?1 AND run = ?2)",
+                params![experiment, run],
+                |row| row.get(0),
+            )
+            .into_error(format!("could not look for run {} of {} in {}", run, experiment, self.path.display()))
+    }
+
+    /// Replaces the data of the run with the given records, in a single transaction
+    pub fn insert_run(&self, experiment: &str, run: &str, records: &RunRecords) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().into_error("could not start transaction")?;
+
+        for table in TABLES {
+            transaction
+                .execute(
+                    &format!("DELETE FROM {} WHERE experiment = ?1 AND run = ?2", table),
+                    params![experiment, run],
+                )
+                .into_error(format!("could not clear table {}", table))?;
+        }
+
+        transaction
+            .execute("INSERT INTO runs VALUES (?1, ?2)", params![experiment, run])
+            .into_error(format!("could not insert run {}", run))?;
+
+        {
+            let mut insert = transaction
+                .prepare("INSERT INTO robots VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")
+                .into_error("could not prepare robots insert")?;
+            for robot in &records.robots {
+                insert
+                    .execute(params![
+                        experiment,
+                        run,
+                        robot.robot_id as i64,
+                        robot.fitness,
+                        robot.start_position.map(|p| p.x),
+                        robot.start_position.map(|p| p.y),
+                        robot.end_position.map(|p| p.x),
+                        robot.end_position.map(|p| p.y),
+                    ])
+                    .into_error(format!("could not insert robot {}", robot.robot_id))?;
+            }
+
+            let mut insert = transaction
+                .prepare("INSERT OR IGNORE INTO snapshots VALUES (?1, ?2, ?3, ?4, ?5)")
+                .into_error("could not prepare snapshots insert")?;
+            for (generation, robot_id, species_id) in &records.snapshots {
+                insert
+                    .execute(params![
+                        experiment,
+                        run,
+                        *generation as i64,
+                        *robot_id as i64,
+                        species_id.map(|id| id as i64),
+                    ])
+                    .into_error(format!("could not insert snapshot of robot {}", robot_id))?;
+            }
+
+            let mut insert = transaction
+                .prepare("INSERT INTO parents VALUES (?1, ?2, ?3, ?4, ?5)")
+                .into_error("could not prepare parents insert")?;
+            for (child_id, parent_id, rank) in &records.parents {
+                insert
+                    .execute(params![experiment, run, *child_id as i64, *parent_id as i64, *rank as i64])
+                    .into_error(format!("could not insert parent of robot {}", child_id))?;
+            }
+
+            for (table, measures) in [
+                ("behaviour_measures", &records.behaviour_measures),
+                ("phenotype_measures", &records.phenotype_measures),
+            ] {
+                let mut insert = transaction
+                    .prepare(&format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5)", table))
+                    .into_error(format!("could not prepare {} insert", table))?;
+                for (robot_id, measure, value) in measures {
+                    insert
+                        .execute(params![experiment, run, *robot_id as i64, measure, value])
+                        .into_error(format!("could not insert {} of robot {}", table, robot_id))?;
+                }
+            }
+        }
+
+        transaction.commit().into_error("could not commit transaction")
+    }
+}
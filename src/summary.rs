@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use crate::error::{ConvertResult, Error};
+use crate::output::with_extension;
+use crate::table::{ColumnDef, ColumnType, Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+    /// Up to date, not consolidated again
+    Skipped,
+}
+
+/// Counts for the runs of an experiment, or for the whole batch
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExperimentSummary {
+    pub experiment: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Generations found in the consolidated runs
+    pub generations: usize,
+    /// Robots of the fitness files of the consolidated runs
+    pub robots: usize,
+    /// Time spent consolidating the runs, summed over the workers
+    pub elapsed_seconds: f64,
+}
+
+impl ExperimentSummary {
+    fn add(&mut self, other: &ExperimentSummary) {
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.generations += other.generations;
+        self.robots += other.robots;
+        self.elapsed_seconds += other.elapsed_seconds;
+    }
+}
+
+/// End of batch summary, one entry per experiment
+#[derive(Debug, Default, Serialize)]
+pub struct BatchSummary {
+    pub experiments: Vec<ExperimentSummary>,
+    pub total: ExperimentSummary,
+    /// Time from the start of the batch to the end of the last run
+    pub wall_seconds: f64,
+    #[serde(skip)]
+    by_experiment: BTreeMap<String, ExperimentSummary>,
+}
+
+impl BatchSummary {
+    pub fn record(&mut self, experiment: &str, status: RunStatus, generations: usize, robots: usize, elapsed: Duration) {
+        let summary = self
+            .by_experiment
+            .entry(experiment.to_string())
+            .or_insert_with(|| ExperimentSummary {
+                experiment: experiment.to_string(),
+                ..Default::default()
+            });
+        match status {
+            RunStatus::Succeeded => summary.succeeded += 1,
+            RunStatus::Failed => summary.failed += 1,
+            RunStatus::Skipped => summary.skipped += 1,
+        }
+        summary.generations += generations;
+        summary.robots += robots;
+        summary.elapsed_seconds += elapsed.as_secs_f64();
+    }
+
+    /// Computes the totals, once all the runs are recorded
+    pub fn finish(&mut self, wall_time: Duration) {
+        self.experiments = self.by_experiment.values().cloned().collect();
+        self.total = ExperimentSummary {
+            experiment: "total".to_string(),
+            ..Default::default()
+        };
+        for experiment in &self.experiments {
+            self.total.add(experiment);
+        }
+        self.wall_seconds = wall_time.as_secs_f64();
+    }
+
+    pub fn any_failed(&self) -> bool {
+        self.total.failed > 0
+    }
+
+    pub fn print(&self) {
+        let width = self
+            .experiments
+            .iter()
+            .map(|e| e.experiment.len())
+            .chain(Some("experiment".len()))
+            .max()
+            .unwrap_or(0);
+        println!(
+            "{:<width$}  {:>9}  {:>6}  {:>7}  {:>11}  {:>8}  {:>9}",
+            "experiment", "succeeded", "failed", "skipped", "generations", "robots", "elapsed",
+            width = width
+        );
+        for summary in self.experiments.iter().chain(Some(&self.total)) {
+            println!(
+                "{:<width$}  {:>9}  {:>6}  {:>7}  {:>11}  {:>8}  {:>8.1}s",
+                summary.experiment,
+                summary.succeeded,
+                summary.failed,
+                summary.skipped,
+                summary.generations,
+                summary.robots,
+                summary.elapsed_seconds,
+                width = width
+            );
+        }
+        println!("Finished in {:.1}s", self.wall_seconds);
+    }
+
+    /// Writes `<stem>.tsv`, one row per experiment, and `<stem>.json` with the totals too
+    pub fn write<P: AsRef<Path>>(&self, stem: P, null_token: &str) -> Result<(), Error> {
+        let stem = stem.as_ref();
+        let mut table = Table::new(vec![
+            ColumnDef::new("experiment", ColumnType::Utf8),
+            ColumnDef::new("succeeded", ColumnType::UInt64),
+            ColumnDef::new("failed", ColumnType::UInt64),
+            ColumnDef::new("skipped", ColumnType::UInt64),
+            ColumnDef::new("generations", ColumnType::UInt64),
+            ColumnDef::new("robots", ColumnType::UInt64),
+            ColumnDef::new("elapsed_seconds", ColumnType::Float64),
+        ]);
+        for summary in &self.experiments {
+            table.push(vec![
+                Value::Str(summary.experiment.clone()),
+                Value::UInt(summary.succeeded as u64),
+                Value::UInt(summary.failed as u64),
+                Value::UInt(summary.skipped as u64),
+                Value::UInt(summary.generations as u64),
+                Value::UInt(summary.robots as u64),
+                Value::Float(summary.elapsed_seconds),
+            ]);
+        }
+        table.write_tsv(with_extension(stem, "tsv"), null_token)?;
+
+        let json_path = with_extension(stem, "json");
+        let summary = serde_json::to_string_pretty(self).into_error("could not serialize batch summary")?;
+        fs::write(&json_path, summary + "\n").into_error(format!("could not write {}", json_path.display()))
+    }
+}
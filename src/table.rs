@@ -0,0 +1,381 @@
+use arrow::array::{Array, ArrayRef, AsArray, Float64Builder, StringBuilder, UInt64Builder};
+use arrow::datatypes::{DataType, Field, Float64Type, Schema, SchemaRef, UInt64Type};
+use arrow::record_batch::RecordBatch;
+use std::io::prelude::*;
+use std::path::Path;
+use std::sync::Arc;
+use std::{fs, io};
+use crate::error::{ConvertResult, Error};
+use crate::iterators::IdentifyLast;
+
+/// Type of the values of a column, every column is nullable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    UInt64,
+    Float64,
+    Utf8,
+}
+
+impl ColumnType {
+    fn from_arrow_type(data_type: &DataType) -> Option<Self> {
+        match data_type {
+            DataType::UInt64 => Some(ColumnType::UInt64),
+            DataType::Float64 => Some(ColumnType::Float64),
+            DataType::Utf8 => Some(ColumnType::Utf8),
+            _ => None,
+        }
+    }
+
+    fn arrow_type(&self) -> DataType {
+        match self {
+            ColumnType::UInt64 => DataType::UInt64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Utf8 => DataType::Utf8,
+        }
+    }
+
+    /// Type that can hold the values of both types
+    fn widen(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::UInt64, ColumnType::Float64) | (ColumnType::Float64, ColumnType::UInt64) => {
+                ColumnType::Float64
+            }
+            _ => ColumnType::Utf8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+impl ColumnDef {
+    pub fn new<S: ToString>(name: S, column_type: ColumnType) -> Self {
+        ColumnDef {
+            name: name.to_string(),
+            column_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    UInt(u64),
+    Float(f64),
+    Str(String),
+}
+
+impl From<Option<u64>> for Value {
+    fn from(value: Option<u64>) -> Self {
+        value.map(Value::UInt).unwrap_or(Value::Null)
+    }
+}
+
+impl From<Option<f64>> for Value {
+    fn from(value: Option<f64>) -> Self {
+        value.map(Value::Float).unwrap_or(Value::Null)
+    }
+}
+
+/// In-memory table with a typed schema, written out in the different output formats
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub columns: Vec<ColumnDef>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<ColumnDef>) -> Self {
+        Table {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        assert_eq!(row.len(), self.columns.len(), "row length does not match the table columns");
+        self.rows.push(row);
+    }
+
+    /// Concatenates the tables of several runs, adding `experiment` and `run` as first columns.
+    /// The columns are the union of the columns of all the tables, missing ones are filled with nulls.
+    /// A column typed differently in several tables is widened to Float64 for integers and floats, to Utf8 otherwise.
+    pub fn combine<'a, I>(tables: I) -> Table
+    where
+        I: IntoIterator<Item = (&'a str, &'a str, &'a Table)>,
+    {
+        let tables: Vec<_> = tables.into_iter().collect();
+        let mut columns = vec![
+            ColumnDef::new("experiment", ColumnType::Utf8),
+            ColumnDef::new("run", ColumnType::Utf8),
+        ];
+        for (_, _, table) in &tables {
+            for column in &table.columns {
+                match columns.iter_mut().find(|c| c.name == column.name) {
+                    // the same column can be typed differently in each run, e.g. inferred extra columns
+                    Some(existing) => existing.column_type = existing.column_type.widen(column.column_type),
+                    None => columns.push(column.clone()),
+                }
+            }
+        }
+
+        let mut combined = Table::new(columns);
+        for (experiment, run, table) in tables {
+            let positions: Vec<Option<usize>> = combined.columns[2..]
+                .iter()
+                .map(|column| table.columns.iter().position(|c| c.name == column.name))
+                .collect();
+            for row in &table.rows {
+                let mut combined_row = vec![Value::Str(experiment.to_string()), Value::Str(run.to_string())];
+                combined_row.extend(
+                    positions
+                        .iter()
+                        .map(|position| position.map(|i| row[i].clone()).unwrap_or(Value::Null)),
+                );
+                combined.rows.push(combined_row);
+            }
+        }
+        combined
+    }
+
+    /// Reads back a table written by `write_tsv`.
+    /// Columns not in `known_columns` are read as Float64, or as Utf8 if some of their values are not numbers.
+    pub fn read_tsv<P: AsRef<Path>>(path: P, null_token: &str, known_columns: &[ColumnDef]) -> Result<Table, Error> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).into_error(format!("could not open {}", path.display()))?;
+        let mut lines = io::BufReader::new(file).lines();
+        let header = match lines.next() {
+            Some(header) => header.into_error(format!("could not read {}", path.display()))?,
+            None => return Ok(Table::new(Vec::new())),
+        };
+        let lines = lines
+            .collect::<io::Result<Vec<String>>>()
+            .into_error(format!("could not read {}", path.display()))?;
+        let columns: Vec<ColumnDef> = header
+            .split('\t')
+            .enumerate()
+            .map(|(i, name)| {
+                known_columns
+                    .iter()
+                    .find(|column| column.name == name)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        let numeric = lines.iter().all(|line| {
+                            line.split('\t')
+                                .nth(i)
+                                .is_none_or(|field| field == null_token || field.parse::<f64>().is_ok())
+                        });
+                        ColumnDef::new(name, if numeric { ColumnType::Float64 } else { ColumnType::Utf8 })
+                    })
+            })
+            .collect();
+
+        let mut table = Table::new(columns);
+        for (line_number, line) in lines.iter().enumerate() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != table.columns.len() {
+                let message = format!("{} fields instead of {}", fields.len(), table.columns.len());
+                return Err(Error::malformed_line(path, line_number + 2, None, message));
+            }
+            let row = fields
+                .iter()
+                .zip(&table.columns)
+                .map(|(field, column)| -> Result<Value, Error> {
+                    if *field == null_token {
+                        return Ok(Value::Null);
+                    }
+                    let malformed = || {
+                        let message = format!("invalid value {:?}", field);
+                        Error::malformed_line(path, line_number + 2, Some(&column.name), message)
+                    };
+                    Ok(match column.column_type {
+                        ColumnType::UInt64 => Value::UInt(field.parse().map_err(|_| malformed())?),
+                        ColumnType::Float64 => Value::Float(field.parse().map_err(|_| malformed())?),
+                        ColumnType::Utf8 => Value::Str(field.to_string()),
+                    })
+                })
+                .collect::<Result<Vec<Value>, Error>>()?;
+            table.rows.push(row);
+        }
+        Ok(table)
+    }
+
+    /// Writes the table as tab separated values with a header line
+    pub fn write_tsv<P: AsRef<Path>>(&self, path: P, null_token: &str) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = fs::File::create(path).into_error(format!("could not create {}", path.display()))?;
+        let mut writer = io::BufWriter::new(file);
+        self.write_tsv_to(&mut writer, null_token)
+            .into_error(format!("could not write {}", path.display()))
+    }
+
+    fn write_tsv_to<W: Write>(&self, writer: &mut W, null_token: &str) -> io::Result<()> {
+        for (last, column) in self.columns.iter().identify_last() {
+            write!(writer, "{}{}", column.name, if last { "\n" } else { "\t" })?;
+        }
+        for row in &self.rows {
+            for (last, value) in row.iter().identify_last() {
+                match value {
+                    Value::Null => write!(writer, "{}", null_token)?,
+                    Value::UInt(v) => write!(writer, "{}", v)?,
+                    Value::Float(v) => write!(writer, "{}", v)?,
+                    Value::Str(v) => write!(writer, "{}", v)?,
+                }
+                write!(writer, "{}", if last { "\n" } else { "\t" })?;
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn arrow_schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(
+            self.columns
+                .iter()
+                .map(|column| Field::new(&column.name, column.column_type.arrow_type(), true))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    /// Builds a table from arrow record batches sharing the schema
+    pub fn from_record_batches(schema: &Schema, batches: &[RecordBatch]) -> Result<Table, Error> {
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|field| match ColumnType::from_arrow_type(field.data_type()) {
+                Some(column_type) => Ok(ColumnDef::new(field.name(), column_type)),
+                None => Err(Error::new(format!(
+                    "column {} has unsupported type {}",
+                    field.name(),
+                    field.data_type()
+                ))),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut table = Table::new(columns);
+        for batch in batches {
+            let mut rows = vec![Vec::with_capacity(table.columns.len()); batch.num_rows()];
+            for (column, array) in table.columns.iter().zip(batch.columns()) {
+                for (i, row) in rows.iter_mut().enumerate() {
+                    row.push(if array.is_null(i) {
+                        Value::Null
+                    } else {
+                        match column.column_type {
+                            ColumnType::UInt64 => Value::UInt(array.as_primitive::<UInt64Type>().value(i)),
+                            ColumnType::Float64 => Value::Float(array.as_primitive::<Float64Type>().value(i)),
+                            ColumnType::Utf8 => Value::Str(array.as_string::<i32>().value(i).to_string()),
+                        }
+                    });
+                }
+            }
+            table.rows.extend(rows);
+        }
+        Ok(table)
+    }
+
+    /// Converts the table to an arrow record batch.
+    /// Values that do not match the column type are stored as null.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, Error> {
+        let arrays: Vec<ArrayRef> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| -> ArrayRef {
+                let values = self.rows.iter().map(|row| &row[i]);
+                match column.column_type {
+                    ColumnType::UInt64 => {
+                        let mut builder = UInt64Builder::with_capacity(self.rows.len());
+                        for value in values {
+                            builder.append_option(match value {
+                                Value::UInt(v) => Some(*v),
+                                _ => None,
+                            });
+                        }
+                        Arc::new(builder.finish())
+                    }
+                    ColumnType::Float64 => {
+                        let mut builder = Float64Builder::with_capacity(self.rows.len());
+                        for value in values {
+                            builder.append_option(match value {
+                                Value::Float(v) => Some(*v),
+                                Value::UInt(v) => Some(*v as f64),
+                                _ => None,
+                            });
+                        }
+                        Arc::new(builder.finish())
+                    }
+                    ColumnType::Utf8 => {
+                        let mut builder = StringBuilder::new();
+                        for value in values {
+                            match value {
+                                Value::Null => builder.append_null(),
+                                Value::UInt(v) => builder.append_value(v.to_string()),
+                                Value::Float(v) => builder.append_value(v.to_string()),
+                                Value::Str(v) => builder.append_value(v),
+                            }
+                        }
+                        Arc::new(builder.finish())
+                    }
+                }
+            })
+            .collect();
+
+        RecordBatch::try_new(self.arrow_schema(), arrays).into_error("could not build arrow record batch")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(column_type: ColumnType, values: Vec<Value>) -> Table {
+        let mut table = Table::new(vec![
+            ColumnDef::new("id", ColumnType::UInt64),
+            ColumnDef::new("n_eval", column_type),
+        ]);
+        for (id, value) in values.into_iter().enumerate() {
+            table.push(vec![Value::UInt(id as u64), value]);
+        }
+        table
+    }
+
+    #[test]
+    fn combine_widens_uint_and_float_columns() {
+        let uints = table(ColumnType::UInt64, vec![Value::UInt(3), Value::UInt(4)]);
+        let floats = table(ColumnType::Float64, vec![Value::Float(3.5), Value::Null]);
+        let combined = Table::combine(vec![("exp1", "1", &uints), ("exp1", "2", &floats)]);
+        assert_eq!(combined.columns[3], ColumnDef::new("n_eval", ColumnType::Float64));
+
+        let batch = combined.to_record_batch().unwrap();
+        let n_eval = batch.column(3).as_primitive::<Float64Type>();
+        assert_eq!(n_eval.iter().collect::<Vec<_>>(), vec![Some(3.0), Some(4.0), Some(3.5), None]);
+    }
+
+    #[test]
+    fn combine_widens_mixed_columns_to_text() {
+        let floats = table(ColumnType::Float64, vec![Value::Float(3.5)]);
+        let texts = table(ColumnType::Utf8, vec![Value::Str("fast".to_string())]);
+        let combined = Table::combine(vec![("exp1", "1", &floats), ("exp1", "2", &texts)]);
+        assert_eq!(combined.columns[3], ColumnDef::new("n_eval", ColumnType::Utf8));
+
+        let batch = combined.to_record_batch().unwrap();
+        let n_eval = batch.column(3).as_string::<i32>();
+        assert_eq!(n_eval.iter().collect::<Vec<_>>(), vec![Some("3.5"), Some("fast")]);
+    }
+
+    #[test]
+    fn combine_fills_missing_columns_with_nulls() {
+        let uints = table(ColumnType::UInt64, vec![Value::UInt(3)]);
+        let ids = Table {
+            columns: vec![ColumnDef::new("id", ColumnType::UInt64)],
+            rows: vec![vec![Value::UInt(7)]],
+        };
+        let combined = Table::combine(vec![("exp1", "1", &ids), ("exp2", "1", &uints)]);
+        assert_eq!(combined.columns[3], ColumnDef::new("n_eval", ColumnType::UInt64));
+        assert_eq!(combined.rows[0][3], Value::Null);
+        assert_eq!(combined.rows[1][3], Value::UInt(3));
+    }
+}
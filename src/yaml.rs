@@ -0,0 +1,294 @@
+use regex::Regex;
+use std::convert::TryFrom;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+use crate::error::Error;
+
+lazy_static! {
+    /// Indentation and list markers, a key and the rest of the line after its colon
+    static ref KEY_LINE_REGEX: Regex = Regex::new(r"^(\s*(?:-\s+)*)([A-Za-z_][A-Za-z0-9_]*):(.*)$").unwrap();
+    /// Indentation and list markers of a list item without a key
+    static ref LIST_ITEM_REGEX: Regex = Regex::new(r"^(\s*(?:-\s+)+)(.*)$").unwrap();
+    /// Header of a literal or folded block scalar, e.g. `|`, `>-` or `|2`
+    static ref BLOCK_SCALAR_REGEX: Regex = Regex::new(r"^[|>][-+0-9]*\s*(?:#.*)?$").unwrap();
+}
+
+/// Rewrites the YAML written by revolve into standard YAML: adds the missing space after the colon of the
+/// keys (`id:3`) and turns python's `None` into `null` when it is a whole value or a flow sequence element.
+/// Values are never rewritten otherwise, e.g. `name:a:b` keeps the value `a:b`, and the content of block
+/// scalars (`|` and `>`) is kept as it is.
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len() + 16);
+    // indentation of the line that opened the block scalar the current line is in
+    let mut block_scalar: Option<usize> = None;
+    for line in text.lines() {
+        let indentation = line.len() - line.trim_start().len();
+        if let Some(block_indentation) = block_scalar {
+            if line.trim().is_empty() || indentation > block_indentation {
+                normalized.push_str(line);
+                normalized.push('\n');
+                continue;
+            }
+            block_scalar = None;
+        }
+        if let Some(captures) = KEY_LINE_REGEX.captures(line) {
+            let value = &captures[3];
+            normalized.push_str(&captures[1]);
+            normalized.push_str(&captures[2]);
+            normalized.push(':');
+            if !value.trim().is_empty() {
+                // keeps the original spacing if there was some
+                let spacing = &value[..value.len() - value.trim_start().len()];
+                normalized.push_str(if spacing.is_empty() { " " } else { spacing });
+                normalized.push_str(&none_to_null(value.trim_start()));
+            }
+            if BLOCK_SCALAR_REGEX.is_match(value.trim()) {
+                block_scalar = Some(captures[1].len());
+            }
+        } else if let Some(captures) = LIST_ITEM_REGEX.captures(line) {
+            normalized.push_str(&captures[1]);
+            normalized.push_str(&none_to_null(&captures[2]));
+            if BLOCK_SCALAR_REGEX.is_match(captures[2].trim()) {
+                block_scalar = Some(indentation);
+            }
+        } else {
+            normalized.push_str(line);
+        }
+        normalized.push('\n');
+    }
+    normalized
+}
+
+/// `null` for a `None` value, or the flow sequence with its `None` elements, at any depth, replaced by `null`.
+/// Everything else, quoted strings and spacing included, is kept as it is.
+fn none_to_null(value: &str) -> String {
+    if value.trim_end() == "None" {
+        return format!("null{}", &value[value.trim_end().len()..]);
+    }
+    if !value.starts_with('[') {
+        return value.to_string();
+    }
+    let mut normalized = String::with_capacity(value.len());
+    let push_element = |normalized: &mut String, element: &str| {
+        if element.trim() == "None" {
+            let leading = element.len() - element.trim_start().len();
+            normalized.push_str(&element[..leading]);
+            normalized.push_str("null");
+            normalized.push_str(&element[leading + "None".len()..]);
+        } else {
+            normalized.push_str(element);
+        }
+    };
+    let mut element_start = 0;
+    let mut quote: Option<char> = None;
+    // elements of flow mappings are not sequence elements
+    let mut mapping_depth = 0usize;
+    let mut chars = value.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            // only double quoted strings have escapes, `''` in single quotes closes and reopens the string
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'') | (None, '"') if value[element_start..i].trim().is_empty() => quote = Some(c),
+            (None, '{') => mapping_depth += 1,
+            (None, '}') => mapping_depth = mapping_depth.saturating_sub(1),
+            (None, '[') | (None, ',') | (None, ']') if mapping_depth == 0 => {
+                push_element(&mut normalized, &value[element_start..i]);
+                normalized.push(c);
+                element_start = i + c.len_utf8();
+            }
+            (None, _) => {}
+        }
+    }
+    push_element(&mut normalized, &value[element_start..]);
+    normalized
+}
+
+/// Parent ids in the `parents` key of a phylogeny file of revolve: a list, a single id, a comma separated
+/// string of ids or `None`
+pub fn parse_parents(path: &Path, text: &str) -> Result<Vec<u64>, Error> {
+    let parse_error = |what: &str| Error::malformed_line(path, 1, Some("parents"), what);
+    let documents = YamlLoader::load_from_str(&normalize(text))
+        .map_err(|e| Error::malformed_line(path, e.marker().line(), None, e.to_string()))?;
+    let parents: Vec<u64> = match documents.first().map(|document| &document["parents"]) {
+        Some(Yaml::Array(array)) => array
+            .iter()
+            .map(|node| {
+                node.as_i64()
+                    .and_then(|id| u64::try_from(id).ok())
+                    .ok_or_else(|| parse_error(&format!("contain a non id value {:?}", node)))
+            })
+            .collect::<Result<_, _>>()?,
+        Some(Yaml::Null) | None => Vec::new(),
+        Some(Yaml::Integer(single_parent)) => vec![u64::try_from(*single_parent)
+            .map_err(|_| parse_error(&format!("is a negative id {}", single_parent)))?],
+        Some(Yaml::String(text)) => text
+            .split(',')
+            .map(|v| v.trim().parse().map_err(|_| parse_error(&format!("invalid parent id {:?}", v))))
+            .collect::<Result<_, _>>()?,
+        Some(Yaml::Real(_)) => return Err(parse_error("yaml parse error: Real")),
+        Some(Yaml::Boolean(_)) => return Err(parse_error("yaml parse error: Boolean")),
+        Some(Yaml::Hash(_)) => return Err(parse_error("yaml parse error: Hash")),
+        Some(Yaml::Alias(_)) => return Err(parse_error("yaml parse error: Alias")),
+        Some(Yaml::BadValue) => return Err(parse_error("yaml parse error: BadValue")),
+    };
+    Ok(parents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parents(text: &str) -> Result<Vec<u64>, Error> {
+        parse_parents(Path::new("parents_1.yaml"), text)
+    }
+
+    #[test]
+    fn normalize_adds_space_after_key() {
+        assert_eq!(normalize("id:3\nage:\n  evaluations:6\n"), "id: 3\nage:\n  evaluations: 6\n");
+    }
+
+    #[test]
+    fn normalize_keeps_existing_spacing() {
+        assert_eq!(normalize("id: 3\nname:   robot\n"), "id: 3\nname:   robot\n");
+    }
+
+    #[test]
+    fn normalize_keeps_colons_in_values() {
+        assert_eq!(normalize("name:a:b\nurl:http://host:80/x\n"), "name: a:b\nurl: http://host:80/x\n");
+    }
+
+    #[test]
+    fn normalize_replaces_whole_none_values() {
+        assert_eq!(normalize("parents:None\n- None\n"), "parents: null\n- null\n");
+        assert_eq!(normalize("parents:[3, None]\n"), "parents: [3, null]\n");
+    }
+
+    #[test]
+    fn normalize_keeps_none_inside_values() {
+        assert_eq!(normalize("label:NoneSuch\nnote:'None of them'\n"), "label: NoneSuch\nnote: 'None of them'\n");
+    }
+
+    #[test]
+    fn normalize_replaces_nested_none_elements() {
+        assert_eq!(normalize("parents:[[1, None], 2]\n"), "parents: [[1, null], 2]\n");
+        assert_eq!(normalize("- [None,None]\n"), "- [null,null]\n");
+    }
+
+    #[test]
+    fn normalize_keeps_quoted_strings_and_spacing() {
+        assert_eq!(normalize("names:['a,b', None]\n"), "names: ['a,b', null]\n");
+        assert_eq!(normalize("names:[\"x, None\",None ]\n"), "names: [\"x, None\",null ]\n");
+        assert_eq!(
+            normalize("names:['it''s, None', \"a\\\"]\", None]\n"),
+            "names: ['it''s, None', \"a\\\"]\", null]\n"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_none_in_flow_mappings() {
+        assert_eq!(normalize("ages:[{a: None}, None]\n"), "ages: [{a: None}, null]\n");
+    }
+
+    #[test]
+    fn normalize_keeps_block_scalars() {
+        let text = "desc: |\n  foo:bar\n\n  - None\nid:3\nnote: >-\n    a:b\n";
+        assert_eq!(normalize(text), "desc: |\n  foo:bar\n\n  - None\nid: 3\nnote: >-\n    a:b\n");
+        assert_eq!(normalize("items:\n- |\n  x:y\n- None\n"), "items:\n- |\n  x:y\n- null\n");
+    }
+
+    #[test]
+    fn normalize_list_of_ids() {
+        assert_eq!(normalize("individuals_ids:\n- 1\n- 2\n"), "individuals_ids:\n- 1\n- 2\n");
+    }
+
+    #[test]
+    fn parents_flow_array() {
+        assert_eq!(parents("parents:[3, 4]").unwrap(), vec![3, 4]);
+    }
+
+    #[test]
+    fn parents_block_array() {
+        assert_eq!(parents("parents:\n- 3\n- 4\n").unwrap(), vec![3, 4]);
+    }
+
+    #[test]
+    fn parents_array_with_non_id() {
+        assert!(parents("parents:[3, None]").is_err());
+        assert!(parents("parents:[3, -4]").is_err());
+    }
+
+    #[test]
+    fn parents_python_none() {
+        assert_eq!(parents("parents:None").unwrap(), Vec::<u64>::new());
+        assert_eq!(parents("parents:None\n").unwrap(), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn parents_null_and_empty_value() {
+        assert_eq!(parents("parents: null\n").unwrap(), Vec::<u64>::new());
+        assert_eq!(parents("parents:\n").unwrap(), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn parents_empty_file() {
+        assert_eq!(parents("").unwrap(), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn parents_single_integer() {
+        assert_eq!(parents("parents:7").unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn parents_negative_integer() {
+        assert!(parents("parents:-7").is_err());
+    }
+
+    #[test]
+    fn parents_comma_separated_string() {
+        assert_eq!(parents("parents:3,4").unwrap(), vec![3, 4]);
+        assert_eq!(parents("parents:3, 4").unwrap(), vec![3, 4]);
+    }
+
+    #[test]
+    fn parents_invalid_string() {
+        assert!(parents("parents:3,x").is_err());
+    }
+
+    #[test]
+    fn parents_real() {
+        assert!(parents("parents:1.5").is_err());
+    }
+
+    #[test]
+    fn parents_boolean() {
+        assert!(parents("parents:true").is_err());
+    }
+
+    #[test]
+    fn parents_hash() {
+        assert!(parents("parents:\n  first:3\n").is_err());
+    }
+
+    #[test]
+    fn parents_alias_is_resolved() {
+        assert_eq!(parents("first: &first 3\nparents: *first\n").unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn parents_missing_key() {
+        assert!(parents("children:[3, 4]").is_err());
+    }
+
+    #[test]
+    fn parents_malformed_yaml_reports_line() {
+        match parents("id: 1\n@parents: 3\nage: 2\n") {
+            Err(Error::MalformedLine { line, .. }) => assert_eq!(line, 2),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}